@@ -0,0 +1,34 @@
+//! Argument types parsed by clap.
+
+use std::fmt;
+use std::str::FromStr;
+
+use obscura_core::{config::Config, Error};
+use obscura_wallet::{
+    units::{self, ParseAmountError},
+    Address,
+};
+
+/// A coin amount given on the command line, held in base units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Amount(pub u64);
+
+impl FromStr for Amount {
+    type Err = ParseAmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        units::parse_amount(s).map(Self)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&units::format_amount(self.0))
+    }
+}
+
+/// Parses a recipient address, which must belong to the network the CLI
+/// runs on.
+pub fn address(s: &str) -> Result<Address, Error> {
+    Address::parse(s, &Config::default().network)
+}
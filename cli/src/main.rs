@@ -1,4 +1,14 @@
-use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use clap::{ArgGroup, Parser, Subcommand};
+use obscura_wallet::Address;
+
+mod args;
+mod miner;
+mod node;
+mod verify;
+
+use args::Amount;
 
 #[derive(Parser)]
 #[command(name = "obscura")]
@@ -11,15 +21,127 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Run a full node
-    Node,
+    Node {
+        /// Ledger snapshot to resume from and save to on Ctrl-C
+        #[arg(long, default_value = "ledger.dat")]
+        ledger: PathBuf,
+    },
     /// Start the miner
-    Miner,
+    Miner {
+        /// Ledger snapshot to extend
+        #[arg(long, default_value = "ledger.dat")]
+        ledger: PathBuf,
+        /// HD wallet state paying the block rewards
+        #[arg(long, default_value = "miner.state")]
+        state: PathBuf,
+        /// Pay each block to a freshly derived address
+        #[arg(long)]
+        rotate: bool,
+        /// Number of blocks to mine
+        #[arg(long, default_value_t = 1)]
+        blocks: u64,
+    },
+    /// Check a hex-encoded block or transaction without a node
+    #[command(group(ArgGroup::new("input").required(true).args(["block", "tx"])))]
+    Verify {
+        /// File holding a hex-encoded block
+        #[arg(long)]
+        block: Option<PathBuf>,
+        /// File holding a hex-encoded transaction
+        #[arg(long)]
+        tx: Option<PathBuf>,
+    },
     /// Wallet operations
-    Wallet,
+    Wallet {
+        #[command(subcommand)]
+        command: WalletCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum WalletCommands {
+    /// Pay `amount` coins to `recipient`
+    Send {
+        /// Amount in coins, e.g. 1.5
+        #[arg(long)]
+        amount: Amount,
+        /// Recipient address
+        #[arg(long = "to", value_parser = args::address)]
+        recipient: Address,
+    },
 }
 
 fn main() {
-    let _cli = Cli::parse();
-    // TODO: dispatch to sub-modules
-    println!("Obscura CLI stub");
+    let cli = Cli::parse();
+    match cli.command {
+        Commands::Wallet { command: WalletCommands::Send { amount, recipient } } => {
+            println!("Would send {amount} to {recipient}");
+        }
+        Commands::Verify { block, tx } => {
+            let (path, is_block) = match (block, tx) {
+                (Some(path), _) => (path, true),
+                (None, path) => (path.expect("clap requires --block or --tx"), false),
+            };
+            match verify::run(&path, is_block) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(err) => {
+                    eprintln!("verify failed: {err}");
+                    std::process::exit(2);
+                }
+            }
+        }
+        Commands::Node { ledger } => {
+            if let Err(err) = node::run(&ledger) {
+                eprintln!("node failed: {err}");
+                std::process::exit(1);
+            }
+        }
+        Commands::Miner { ledger, state, rotate, blocks } => {
+            if let Err(err) = miner::run(&ledger, &state, rotate, blocks) {
+                eprintln!("miner failed: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send(amount: &str, to: &str) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(["obscura", "wallet", "send", "--amount", amount, "--to", to])
+    }
+
+    fn address(network: &str) -> String {
+        Address::from_pubkey_hash(network, &[0x11; 20]).unwrap().to_string()
+    }
+
+    #[test]
+    fn send_arguments_are_validated() {
+        let to = &address("main");
+        let Commands::Wallet { command: WalletCommands::Send { amount, recipient } } = send("1.5", to).unwrap().command else {
+            panic!("expected wallet send");
+        };
+        assert_eq!(amount, Amount(150_000_000));
+        assert_eq!(&recipient.to_string(), to);
+
+        let err = send("abc", to).err().unwrap();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ValueValidation);
+        assert!(err.to_string().contains("invalid amount"));
+        assert!(send("1", "not-an-address").is_err());
+        let err = send("1", &address("test")).err().unwrap();
+        assert!(err.to_string().contains("different network"), "{err}");
+    }
+
+    #[test]
+    fn corrupted_address_checksum_is_rejected() {
+        let mut to = address("main");
+        let last = to.pop().unwrap();
+        to.push(if last == '2' { '3' } else { '2' });
+        let err = send("1", &to).err().unwrap();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ValueValidation);
+        assert!(err.to_string().contains("invalid address"), "{err}");
+    }
 }
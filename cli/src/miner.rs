@@ -0,0 +1,135 @@
+//! The `miner` command: mines blocks on the local ledger, paying each
+//! coinbase to an address derived from the miner's HD wallet.
+//!
+//! The wallet lives in a small state file holding the 32-byte seed followed
+//! by the big-endian index of the next unused key.  Without `--rotate` every
+//! block pays key 0; with it each block pays a fresh key, so the miner's
+//! rewards cannot be linked to one another by address.
+
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use obscura_core::{ledger::Ledger, template::TemplateBuilder, Block};
+use obscura_wallet::{Address, HdWallet};
+
+/// Hands out coinbase addresses, persisting the derivation index.
+pub struct RewardAddresses {
+    hd: HdWallet,
+    rotate: bool,
+    path: PathBuf,
+}
+
+impl RewardAddresses {
+    /// Loads the wallet state at `path`, creating and saving a fresh random
+    /// wallet there if the file does not exist.
+    pub fn open(path: &Path, rotate: bool) -> io::Result<Self> {
+        let (hd, created) = match fs::read(path) {
+            Ok(bytes) if bytes.len() == 36 => {
+                let seed = bytes[..32].try_into().expect("32 bytes");
+                (HdWallet::resume(seed, u32::from_be_bytes(bytes[32..].try_into().expect("4 bytes"))), false)
+            }
+            Ok(_) => return Err(io::Error::new(ErrorKind::InvalidData, "malformed miner state")),
+            Err(e) if e.kind() == ErrorKind::NotFound => (HdWallet::generate(), true),
+            Err(e) => return Err(e),
+        };
+        let addresses = Self { hd, rotate, path: path.to_path_buf() };
+        if created {
+            addresses.save()?;
+        }
+        Ok(addresses)
+    }
+
+    /// Writes the state next to `path`, syncs it and renames it over the
+    /// old file, so a crash mid-save never loses the seed.
+    fn save(&self) -> io::Result<()> {
+        let mut bytes = self.hd.seed().to_vec();
+        bytes.extend_from_slice(&self.hd.next_index().to_be_bytes());
+        let tmp = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    /// The address on `network` the next block should pay.  Under rotation
+    /// the index is saved before the address is returned, so a crash never
+    /// reuses it.
+    pub fn next(&mut self, network: &str) -> io::Result<Address> {
+        if !self.rotate {
+            return Ok(Address::from_pubkey(network, self.hd.derive(0).public.as_bytes()));
+        }
+        let (_, kp) = self.hd.next_keypair();
+        self.save()?;
+        Ok(Address::from_pubkey(network, kp.public.as_bytes()))
+    }
+}
+
+/// Mines one block on top of `ledger` paying the next reward address and
+/// applies it.
+pub fn mine_block(ledger: &mut Ledger, addresses: &mut RewardAddresses) -> io::Result<Block> {
+    let address = addresses.next(&ledger.config.network)?;
+    let (block, stats) = TemplateBuilder::new(ledger, address.pubkey_hash())
+        .finish()
+        .map_err(io::Error::other)?
+        .mine_with_stats(ledger.config.pow_algo, ledger.config.pow_mode);
+    ledger.apply(&block).map_err(io::Error::other)?;
+    println!(
+        "Mined block {} paying {address}: nonce {}, {} zero bits, {:.0} H/s",
+        block.header.index,
+        stats.nonce,
+        stats.achieved_difficulty,
+        stats.hash_rate()
+    );
+    Ok(block)
+}
+
+/// Mines `blocks` blocks on the ledger at `ledger_path` and saves it.
+pub fn run(ledger_path: &Path, state_path: &Path, rotate: bool, blocks: u64) -> io::Result<()> {
+    let mut ledger = crate::node::open(ledger_path).map_err(io::Error::other)?;
+    let mut addresses = RewardAddresses::open(state_path, rotate)?;
+    for _ in 0..blocks {
+        mine_block(&mut ledger, &mut addresses)?;
+    }
+    crate::node::shutdown(&ledger, ledger_path).map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use obscura_core::config::Config;
+
+    fn reward_pkhs(rotate: bool) -> Vec<Vec<u8>> {
+        let path = std::env::temp_dir().join(format!("obscura-miner-{}-{rotate}.state", std::process::id()));
+        let mut ledger = Ledger::with_config(&Config::default().genesis_block(), Config::default()).unwrap();
+        let mut addresses = RewardAddresses::open(&path, rotate).unwrap();
+        let pkhs = (0..2)
+            .map(|_| mine_block(&mut ledger, &mut addresses).unwrap().transactions[0].outputs[0].pubkey_hash.clone())
+            .collect();
+        fs::remove_file(path).unwrap();
+        pkhs
+    }
+
+    #[test]
+    fn rotation_pays_a_fresh_address_per_block() {
+        let rotating = reward_pkhs(true);
+        assert_ne!(rotating[0], rotating[1]);
+        let fixed = reward_pkhs(false);
+        assert_eq!(fixed[0], fixed[1]);
+    }
+
+    #[test]
+    fn reopening_leaves_the_state_file_untouched() {
+        let path = std::env::temp_dir().join(format!("obscura-miner-{}-reopen.state", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let seed = RewardAddresses::open(&path, false).unwrap().hd.seed().to_vec();
+        assert_eq!(fs::read(&path).unwrap()[..32], seed[..]);
+
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        RewardAddresses::open(&path, false).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+        assert!(!path.with_extension("tmp").exists());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,65 @@
+//! The `node` command: keeps a ledger until interrupted, then flushes it.
+
+use std::io;
+use std::path::Path;
+
+use obscura_core::{config::Config, ledger::Ledger, Result};
+
+/// Resumes from the snapshot at `path`, or starts from the default genesis
+/// if there is none yet.  A snapshot of another network is refused with
+/// [`obscura_core::Error::ConfigMismatch`].
+pub fn open(path: &Path) -> Result<Ledger> {
+    let config = Config::default();
+    if path.exists() {
+        return Ledger::load_from_path(path, config);
+    }
+    Ledger::with_config(&config.genesis_block(), config)
+}
+
+/// Flushes `ledger` to `path`; run on Ctrl-C before the process exits.
+pub fn shutdown(ledger: &Ledger, path: &Path) -> Result<()> {
+    ledger.save_to_path(path)?;
+    println!("Saved ledger at height {} to {}", ledger.height, path.display());
+    Ok(())
+}
+
+/// Runs the node until Ctrl-C, then saves the ledger to `path`.
+pub fn run(path: &Path) -> io::Result<()> {
+    let ledger = open(path).map_err(io::Error::other)?;
+    println!("Node running at height {}; press Ctrl-C to stop", ledger.height);
+    tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()?
+        .block_on(tokio::signal::ctrl_c())?;
+    shutdown(&ledger, path).map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use obscura_core::{config::ConfigBuilder, Error};
+
+    #[test]
+    fn shutdown_writes_ledger() {
+        let path = std::env::temp_dir().join(format!("obscura-node-{}.dat", std::process::id()));
+        let ledger = open(&path).unwrap();
+        assert!(!path.exists());
+
+        shutdown(&ledger, &path).unwrap();
+        let reopened = open(&path).unwrap();
+        assert_eq!((reopened.height, reopened.tip), (ledger.height, ledger.tip));
+        assert_eq!(reopened.commitment(), ledger.commitment());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn open_refuses_another_networks_snapshot() {
+        let path = std::env::temp_dir().join(format!("obscura-node-{}-test.dat", std::process::id()));
+        let config = ConfigBuilder::new().network("test").finish();
+        let ledger = Ledger::with_config(&config.genesis_block(), config).unwrap();
+        ledger.save_to_path(&path).unwrap();
+
+        assert_eq!(open(&path).err(), Some(Error::ConfigMismatch));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+//! The `verify` command: decodes a hex-encoded block or transaction and
+//! runs the context-free checks on it, without a node.
+
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use obscura_core::{config::Config, Result};
+
+/// Report on `bytes` as a block: its hash, size and whether it passes
+/// [`Block::verify_full`](obscura_core::Block::verify_full).
+pub fn block_report(bytes: &[u8], config: &Config) -> (String, bool) {
+    let block = match config.codec.codec().decode_block(bytes) {
+        Ok(block) => block,
+        Err(err) => return (format!("block: undecodable ({err})\nvalid: no\n"), false),
+    };
+    let mut report = String::new();
+    writeln!(report, "block: {}", hex::encode(block.hash())).unwrap();
+    writeln!(report, "height: {}", block.header.index).unwrap();
+    writeln!(report, "size: {} bytes", bytes.len()).unwrap();
+    writeln!(report, "transactions: {}", block.transactions.len()).unwrap();
+    finish(report, block.verify_full(config))
+}
+
+/// Report on `bytes` as a transaction: its txid, size and whether it
+/// passes the checks that need no UTXO set.
+pub fn tx_report(bytes: &[u8], config: &Config) -> (String, bool) {
+    let tx = match config.codec.codec().decode_transaction(bytes) {
+        Ok(tx) => tx,
+        Err(err) => return (format!("transaction: undecodable ({err})\nvalid: no\n"), false),
+    };
+    let mut report = String::new();
+    writeln!(report, "transaction: {}", hex::encode(tx.hash())).unwrap();
+    writeln!(report, "size: {} bytes", bytes.len()).unwrap();
+    writeln!(report, "inputs: {}", tx.input_count()).unwrap();
+    writeln!(report, "outputs: {}", tx.output_count()).unwrap();
+    finish(report, tx.validate_structure(config).and_then(|()| tx.verify_no_duplicate_inputs()))
+}
+
+fn finish(mut report: String, result: Result<()>) -> (String, bool) {
+    match &result {
+        Ok(()) => report.push_str("valid: yes\n"),
+        Err(err) => writeln!(report, "valid: no ({err})").unwrap(),
+    }
+    (report, result.is_ok())
+}
+
+/// Reads the hex file at `path`, prints the report and returns whether the
+/// input was valid.
+pub fn run(path: &Path, is_block: bool) -> io::Result<bool> {
+    let text = fs::read_to_string(path)?;
+    let bytes = hex::decode(text.trim()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let config = Config::default();
+    let (report, valid) = if is_block { block_report(&bytes, &config) } else { tx_report(&bytes, &config) };
+    print!("{report}");
+    Ok(valid)
+}
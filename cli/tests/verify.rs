@@ -0,0 +1,36 @@
+use std::process::Command;
+
+use obscura_core::{config::Config, Transaction, TxOutput};
+
+fn verify(flag: &str, bytes: &[u8], name: &str) -> (String, bool) {
+    let path = std::env::temp_dir().join(format!("obscura-verify-{}-{name}.hex", std::process::id()));
+    std::fs::write(&path, hex::encode(bytes)).unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_obscura")).args(["verify", flag]).arg(&path).output().unwrap();
+    std::fs::remove_file(path).unwrap();
+    (String::from_utf8(out.stdout).unwrap(), out.status.success())
+}
+
+#[test]
+fn valid_block_reported_valid() {
+    let config = Config::default();
+    let block = config.genesis_block();
+    let bytes = config.codec.codec().encode_block(&block).unwrap();
+    let (report, ok) = verify("--block", &bytes, "block");
+    assert!(ok, "{report}");
+    assert!(report.contains(&hex::encode(block.hash())));
+    assert!(report.contains("valid: yes"));
+
+    let mut broken = block;
+    broken.header.merkle_root = [0; 32];
+    let (report, ok) = verify("--block", &config.codec.codec().encode_block(&broken).unwrap(), "broken");
+    assert!(!ok);
+    assert!(report.contains("valid: no (merkle root mismatch)"), "{report}");
+}
+
+#[test]
+fn transaction_report() {
+    let tx = Transaction::coinbase(2, vec![TxOutput { value: 50, pubkey_hash: vec![1] }], b"");
+    let (report, ok) = verify("--tx", &Config::default().codec.codec().encode_transaction(&tx).unwrap(), "tx");
+    assert!(ok, "{report}");
+    assert!(report.contains(&format!("transaction: {}", hex::encode(tx.hash()))));
+}
@@ -0,0 +1,157 @@
+//! Emits deterministic JSON test vectors for other implementations.
+//!
+//! ```text
+//! cargo run --example gen_vectors > vectors.json
+//! ```
+//!
+//! * `keys` – Ed25519 keypairs from fixed seeds with their `pubkey_hash`
+//!   and mainnet/testnet addresses.
+//! * `transactions` – signed spends: the encoding whose `hash256` is the
+//!   txid, the signing message and the txid itself.
+//! * `blocks` – a genesis paying every key and a block carrying the spends:
+//!   header preimage, wire header, block hash and full block encoding.
+//!
+//! All byte strings are hex.  Nothing depends on the clock or an RNG, so
+//! the output only changes when a consensus encoding does.
+
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+use obscura_core::{
+    address::Address, codec::CodecKind, hash160, Block, Transaction, TxInput, TxOutput, BLOCK_VERSION,
+};
+use serde_json::{json, Value};
+
+const SEEDS: [u8; 3] = [1, 2, 3];
+
+fn keypair(seed: u8) -> Keypair {
+    let secret = SecretKey::from_bytes(&[seed; 32]).expect("32-byte seed");
+    let public = PublicKey::from(&secret);
+    Keypair { secret, public }
+}
+
+fn pay(kp: &Keypair, value: u64) -> TxOutput {
+    TxOutput { value, pubkey_hash: hash160(kp.public.as_bytes()).to_vec() }
+}
+
+fn block_at(index: u64, prev_hash: [u8; 32], transactions: Vec<Transaction>) -> Block {
+    let mut block = Block::new(index, prev_hash, transactions, 0);
+    block.header.timestamp = 1_700_000_000 + 60 * index;
+    block
+}
+
+/// The genesis and the block spending its outputs, as described in the
+/// module docs.
+fn chain() -> (Block, Block) {
+    let keys: Vec<Keypair> = SEEDS.iter().map(|&s| keypair(s)).collect();
+    let coinbase = Transaction::coinbase(1, keys.iter().map(|kp| pay(kp, 50)).collect(), b"vectors");
+    let genesis = block_at(1, [0; 32], vec![coinbase.clone()]);
+
+    // Key `i` pays 45 of its genesis output to key `i + 1`, leaving 5 fee.
+    let mut txs = vec![Transaction::coinbase(2, vec![pay(&keys[0], 65)], b"")];
+    for (i, kp) in keys.iter().enumerate() {
+        let input = TxInput::new(coinbase.hash(), i as u32, kp.public.as_bytes().to_vec(), vec![]).expect("valid input");
+        let mut tx = Transaction { inputs: vec![input], outputs: vec![pay(&keys[(i + 1) % keys.len()], 45)], metadata: None };
+        tx.inputs[0].signature = kp.sign(&tx.signing_message()).to_bytes().to_vec();
+        txs.push(tx);
+    }
+    let block = block_at(2, genesis.hash(), txs);
+    (genesis, block)
+}
+
+fn vectors() -> Value {
+    let codec = CodecKind::Bincode.codec();
+    let keys: Vec<Value> = SEEDS
+        .iter()
+        .map(|&seed| {
+            let kp = keypair(seed);
+            json!({
+                "seed": hex::encode([seed; 32]),
+                "public_key": hex::encode(kp.public.as_bytes()),
+                "pubkey_hash": hex::encode(hash160(kp.public.as_bytes())),
+                "address_main": Address::from_pubkey("main", kp.public.as_bytes()).to_string(),
+                "address_test": Address::from_pubkey("test", kp.public.as_bytes()).to_string(),
+            })
+        })
+        .collect();
+    let (genesis, block) = chain();
+    let transactions: Vec<Value> = block.transactions[1..]
+        .iter()
+        .map(|tx| {
+            json!({
+                "encoded": hex::encode(codec.encode_transaction(tx).expect("encodable")),
+                "signing_message": hex::encode(tx.signing_message()),
+                "txid": hex::encode(tx.hash()),
+            })
+        })
+        .collect();
+    let blocks: Vec<Value> = [genesis, block]
+        .iter()
+        .map(|b| {
+            json!({
+                "header": hex::encode(codec.encode_header(&b.header).expect("encodable")),
+                "wire_header": hex::encode(b.header.to_wire()),
+                "hash": hex::encode(b.hash()),
+                "encoded": hex::encode(codec.encode_block(b).expect("encodable")),
+            })
+        })
+        .collect();
+    json!({
+        "block_version": BLOCK_VERSION,
+        "keys": keys,
+        "transactions": transactions,
+        "blocks": blocks,
+    })
+}
+
+fn main() {
+    println!("{}", serde_json::to_string_pretty(&vectors()).expect("serialisable"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use obscura_core::{crypto, hash256, ledger::Ledger};
+
+    fn bytes(v: &Value) -> Vec<u8> {
+        hex::decode(v.as_str().expect("hex string")).expect("valid hex")
+    }
+
+    #[test]
+    fn vectors_reverify() {
+        let vectors = vectors();
+        let codec = CodecKind::Bincode.codec();
+
+        for key in vectors["keys"].as_array().unwrap() {
+            let seed = bytes(&key["seed"]);
+            let kp = keypair(seed[0]);
+            assert_eq!(bytes(&key["public_key"]), kp.public.as_bytes());
+            let address = Address::parse(key["address_test"].as_str().unwrap(), "test").unwrap();
+            assert_eq!(address.pubkey_hash(), bytes(&key["pubkey_hash"]));
+        }
+
+        let ed25519 = crypto::scheme(crypto::ED25519).unwrap();
+        for vector in vectors["transactions"].as_array().unwrap() {
+            let encoded = bytes(&vector["encoded"]);
+            let tx = codec.decode_transaction(&encoded).unwrap();
+            assert_eq!(hash256(&encoded).to_vec(), bytes(&vector["txid"]));
+            assert_eq!(tx.signing_message().to_vec(), bytes(&vector["signing_message"]));
+            for inp in &tx.inputs {
+                assert!(ed25519.verify(&inp.pubkey, &tx.signing_message(), &inp.signature));
+            }
+        }
+
+        let blocks: Vec<Block> = vectors["blocks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| {
+                let block = codec.decode_block(&bytes(&v["encoded"])).unwrap();
+                assert_eq!(hash256(&bytes(&v["header"])).to_vec(), bytes(&v["hash"]));
+                assert_eq!(block.header.to_wire().to_vec(), bytes(&v["wire_header"]));
+                block
+            })
+            .collect();
+        let mut ledger = Ledger::new(&blocks[0]).unwrap();
+        assert_eq!(ledger.apply_block(&blocks[1]).unwrap().fees_collected, 15);
+        assert_eq!(vectors, super::vectors(), "deterministic");
+    }
+}
@@ -0,0 +1,16 @@
+//! Feeds arbitrary bytes through block decoding and full context-free
+//! validation.  Any panic is a bug; malformed input must yield `Err`.
+//!
+//! Run with `cargo fuzz run block_decode fuzz/corpus/block_decode` from
+//! `core/`.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use obscura_core::{config::Config, Block};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(block) = bincode::deserialize::<Block>(data) {
+        let _ = block.hash();
+        let _ = block.verify_full(&Config::default());
+    }
+});
@@ -0,0 +1,111 @@
+//! Network-versioned payment addresses.
+//!
+//! An [`Address`] is a one-byte version followed by the 20-byte `hash160`
+//! of a public key, written in Base58Check: the 21 bytes plus the first four
+//! bytes of their double SHA-256 as a checksum, so a mistyped address fails
+//! to parse instead of paying someone else.  The version is derived from
+//! [`Config::network`](crate::config::Config::network), so an address meant
+//! for one network cannot be mistaken for one on another, and parsing
+//! always names the network expected:
+//!
+//! | Network        | Version | Leading character |
+//! |----------------|---------|-------------------|
+//! | `main`         | `0x00`  | `1`               |
+//! | anything else  | `0x6F`  | `m` or `n`        |
+
+use core::fmt;
+
+use crate::{hash160, Error, Result};
+
+/// Version byte of mainnet addresses.
+pub const MAINNET_VERSION: u8 = 0x00;
+
+/// Version byte of testnet (and every other non-main network's) addresses.
+pub const TESTNET_VERSION: u8 = 0x6F;
+
+/// Address version byte used on `network`.
+pub fn version_for(network: &str) -> u8 {
+    match network {
+        "main" => MAINNET_VERSION,
+        _ => TESTNET_VERSION,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Address {
+    pub version: u8,
+    pub hash: [u8; 20],
+}
+
+impl Address {
+    /// The address paying `pubkey` on `network`.
+    pub fn from_pubkey(network: &str, pubkey: &[u8]) -> Self {
+        Self { version: version_for(network), hash: hash160(pubkey) }
+    }
+
+    /// The address on `network` whose outputs carry `pubkey_hash`, or
+    /// `None` if that is not 20 bytes long.
+    pub fn from_pubkey_hash(network: &str, pubkey_hash: &[u8]) -> Option<Self> {
+        Some(Self { version: version_for(network), hash: pubkey_hash.try_into().ok()? })
+    }
+
+    /// Parses `s` and checks that it belongs to `network`.
+    ///
+    /// Errors with [`Error::InvalidAddress`] if `s` is malformed and
+    /// [`Error::WrongNetwork`] if it is an address of another network.
+    pub fn parse(s: &str, network: &str) -> Result<Self> {
+        let bytes = bs58::decode(s).with_check(None).into_vec().map_err(|_| Error::InvalidAddress)?;
+        let (&version, hash) = bytes.split_first().ok_or(Error::InvalidAddress)?;
+        let hash = hash.try_into().map_err(|_| Error::InvalidAddress)?;
+        if version != MAINNET_VERSION && version != TESTNET_VERSION {
+            return Err(Error::InvalidAddress);
+        }
+        if version != version_for(network) {
+            return Err(Error::WrongNetwork);
+        }
+        Ok(Self { version, hash })
+    }
+
+    /// The `pubkey_hash` an output paying this address carries.
+    pub fn pubkey_hash(&self) -> Vec<u8> {
+        self.hash.to_vec()
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bytes = [0u8; 21];
+        bytes[0] = self.version;
+        bytes[1..].copy_from_slice(&self.hash);
+        f.write_str(&bs58::encode(bytes).with_check().into_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testnet_address_rejected_on_mainnet() {
+        let test = Address::from_pubkey("test", &[7; 32]);
+        let main = Address::from_pubkey("main", &[7; 32]);
+        assert_eq!(test.hash, main.hash);
+        assert!(test.to_string().starts_with(['m', 'n']) && main.to_string().starts_with('1'));
+
+        assert_eq!(Address::parse(&test.to_string(), "test"), Ok(test));
+        assert_eq!(Address::parse(&test.to_string(), "main"), Err(Error::WrongNetwork));
+        assert_eq!(Address::parse(&main.to_string(), "main"), Ok(main));
+        let unknown = bs58::encode([[1].as_slice(), &main.hash].concat()).with_check().into_string();
+        assert_eq!(Address::parse(&unknown, "main"), Err(Error::InvalidAddress));
+        assert_eq!(Address::parse("1abc", "main"), Err(Error::InvalidAddress));
+    }
+
+    #[test]
+    fn corrupted_checksum_is_rejected() {
+        let mut address = Address::from_pubkey("main", &[7; 32]).to_string();
+        // Swap the last character for another valid Base58 one.
+        let last = address.pop().unwrap();
+        address.push(if last == '2' { '3' } else { '2' });
+        assert_eq!(Address::parse(&address, "main"), Err(Error::InvalidAddress));
+    }
+}
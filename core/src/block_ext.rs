@@ -6,13 +6,28 @@
 //!
 //! * [`Block::is_valid`] – lightweight validation against PoW target, Merkle
 //!   root and chain linkage.
-//! * [`Block::mine`] – naïve single-threaded mining loop suitable for testing.
+//! * [`Block::mine`] – naïve single-threaded mining loop suitable for testing;
+//!   [`Block::mine_with_stats`] additionally reports the solution found.
+//! * [`Block::verify_full`] – every check that needs no chain state; safe to
+//!   run on untrusted, freshly decoded blocks.
+//! * [`Block::set_extra_nonce`] – rolls the coinbase once the header nonce
+//!   space is exhausted.
+//! * [`Block::total_output_value`] / [`Block::coinbase_value`] – value sums
+//!   for supply audits.
+//! * `Block::test_block` – a ready-to-apply block for tests (unit tests and
+//!   the `test-utils` feature only).
 //!
 //! Production code will replace `mine` with an async, multi-threaded miner and
 //! `is_valid` will be expanded to enforce timestamp drift, difficulty limits
 //! and consensus rules.
 
-use crate::{pow, Hash, Block};
+use std::time::Instant;
+
+use crate::{
+    config::Config,
+    pow::{self, MiningStats, PowAlgo, PowMode},
+    validation, Block, Error, Hash, Result,
+};
 
 impl Block {
     /// Returns `true` if the block header hash meets difficulty and structural
@@ -31,10 +46,188 @@ impl Block {
     /// Useful in unit tests where deterministic runtime is not critical.  The
     /// function consumes `self` and returns the mined block to avoid accidental
     /// reuse of a partially-modified instance.
-    pub fn mine(mut self) -> Self {
-        while !pow::hash_meets_difficulty(&self.hash(), self.header.difficulty) {
+    pub fn mine(self) -> Self {
+        self.mine_with(PowAlgo::default())
+    }
+
+    /// Same as [`Block::mine`] but searches for a nonce satisfying `algo`.
+    pub fn mine_with(self, algo: PowAlgo) -> Self {
+        self.mine_in(algo, PowMode::BitCount)
+    }
+
+    /// Same as [`Block::mine_with`] but interprets the header difficulty
+    /// under `mode`.
+    pub fn mine_in(self, algo: PowAlgo, mode: PowMode) -> Self {
+        self.mine_with_stats(algo, mode).0
+    }
+
+    /// Same as [`Block::mine_in`] but also reports the winning nonce and
+    /// hash, the difficulty that hash achieved and the hash rate.
+    pub fn mine_with_stats(mut self, algo: PowAlgo, mode: PowMode) -> (Self, MiningStats) {
+        let check = pow::DifficultyCheck::with_mode(self.header.difficulty, mode);
+        let start = Instant::now();
+        let mut hashes = 1;
+        let mut hash = self.header.pow_hash(algo);
+        while !check.check(&hash) {
             self.header.nonce = self.header.nonce.wrapping_add(1);
+            hash = self.header.pow_hash(algo);
+            hashes += 1;
+        }
+        let stats = MiningStats {
+            hashes,
+            elapsed: start.elapsed(),
+            nonce: self.header.nonce,
+            hash,
+            achieved_difficulty: pow::difficulty_of_hash(&hash),
+        };
+        (self, stats)
+    }
+
+    /// Runs every validation rule that does not depend on chain state:
+    /// header version, transaction count and sizes, parent-before-child
+    /// ordering, the Merkle commitment, input signatures and proof-of-work
+    /// under `config`.
+    ///
+    /// Never panics, whatever the block contents; malformed blocks yield an
+    /// `Err`.  Linkage, timestamps and UTXO checks are left to
+    /// [`crate::ledger::Ledger::apply_block`].
+    pub fn verify_full(&self, config: &Config) -> Result<()> {
+        self.header.check_version()?;
+        if self.transactions.is_empty() {
+            return Err(Error::MissingCoinbase);
+        }
+        for tx in &self.transactions {
+            tx.validate_structure(config)?;
+        }
+        validation::check_tx_order(&self.transactions)?;
+        if Self::calc_merkle_root(&self.transactions) != self.header.merkle_root {
+            return Err(Error::MerkleMismatch);
+        }
+        if config.pow_mode.bit_difficulty(self.header.difficulty) < config.min_difficulty
+            || !pow::header_meets_difficulty_in(&self.header, config.pow_algo, config.pow_mode)
+        {
+            return Err(Error::DifficultyFail);
+        }
+        self.transactions[1..].iter().try_for_each(|tx| validation::verify_signatures(tx, config))
+    }
+
+    /// Writes `n` into the coinbase extra-nonce and recomputes
+    /// `header.merkle_root`, giving the miner a fresh header nonce space.
+    ///
+    /// Coinbase metadata shorter than the [`crate::Transaction::coinbase`]
+    /// layout is zero-padded to it first.  Does nothing for a block without
+    /// transactions.
+    pub fn set_extra_nonce(&mut self, n: u64) {
+        let Some(coinbase) = self.transactions.first_mut() else {
+            return;
+        };
+        let meta = coinbase.metadata.get_or_insert_with(Vec::new);
+        if meta.len() < 16 {
+            meta.resize(16, 0);
+        }
+        meta[8..16].copy_from_slice(&n.to_be_bytes());
+        self.header.merkle_root = Self::calc_merkle_root(&self.transactions);
+    }
+
+    /// Sum of every output of every transaction, coinbase included.
+    ///
+    /// Errors with [`Error::ValueOverflow`] if the sum exceeds `u64`.
+    pub fn total_output_value(&self) -> Result<u64> {
+        self.transactions
+            .iter()
+            .try_fold(0u64, |acc, tx| acc.checked_add(tx.total_output_value()?))
+            .ok_or(Error::ValueOverflow)
+    }
+
+    /// Sum of the coinbase's outputs; `None` if the first transaction is
+    /// not a coinbase or its outputs overflow `u64`.
+    pub fn coinbase_value(&self) -> Option<u64> {
+        self.transactions.first().filter(|tx| tx.inputs.is_empty())?.total_output_value()
+    }
+}
+
+/// `pubkey_hash` paid by [`Block::test_block`].
+#[cfg(any(test, feature = "test-utils"))]
+pub const TEST_PUBKEY_HASH: [u8; 20] = [0x7E; 20];
+
+/// A valid Ed25519 public key (the curve's base point) for test inputs
+/// spent without a signature under `Config::allow_unsigned`.
+#[cfg(any(test, feature = "test-utils"))]
+pub const TEST_PUBKEY: [u8; 32] = {
+    let mut key = [0x66; 32];
+    key[0] = 0x58;
+    key
+};
+
+#[cfg(any(test, feature = "test-utils"))]
+impl Block {
+    /// A block at `index` on top of `prev_hash` whose only transaction is a
+    /// coinbase paying the default subsidy to [`TEST_PUBKEY_HASH`].
+    ///
+    /// Difficulty is 0 and the timestamp is `index` minutes after the
+    /// default genesis timestamp, so the block is deterministic and a chain
+    /// of them applies under [`Config::default`].
+    /// Index 1 yields a valid genesis.
+    pub fn test_block(index: u64, prev_hash: Hash) -> Self {
+        let config = Config::default();
+        let reward = crate::TxOutput { value: config.block_subsidy(index), pubkey_hash: TEST_PUBKEY_HASH.to_vec() };
+        let txs = vec![crate::Transaction::coinbase(index, vec![reward], b"test")];
+        let root = Self::calc_merkle_root(&txs);
+        Self::new_with_root(index, prev_hash, txs, 0, root, config.genesis_timestamp + 60 * index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TEST_PUBKEY_HASH;
+    use crate::{ledger::Ledger, Block, Error, Transaction, TxInput, TxOutput};
+
+    #[test]
+    fn extra_nonce_changes_coinbase_and_merkle_root() {
+        let coinbase = Transaction::coinbase(2, vec![TxOutput { value: 50, pubkey_hash: vec![1] }], b"hi");
+        let mut block = Block::new(2, [0u8; 32], vec![coinbase], 0);
+        let (txid, root) = (block.transactions[0].hash(), block.header.merkle_root);
+        assert_eq!(block.transactions[0].coinbase_extra_nonce(), Some(0));
+
+        block.set_extra_nonce(7);
+        assert_eq!(block.transactions[0].coinbase_extra_nonce(), Some(7));
+        assert_eq!(block.transactions[0].coinbase_message(), Some(&b"hi"[..]));
+        assert_ne!(block.transactions[0].hash(), txid);
+        assert_ne!(block.header.merkle_root, root);
+        assert!(block.is_valid(&[0u8; 32]));
+    }
+
+    #[test]
+    fn output_and_coinbase_values() {
+        let pay = |value| TxOutput { value, pubkey_hash: vec![1] };
+        let genesis = Block::new(1, [0u8; 32], vec![Transaction::coinbase(1, vec![pay(30), pay(20)], b"")], 0);
+        assert_eq!(genesis.total_output_value(), Ok(50));
+        assert_eq!(genesis.coinbase_value(), Some(50));
+
+        let spend = |prev, values: &[u64]| Transaction {
+            inputs: vec![TxInput { prev_tx: prev, output_index: 0, pubkey: vec![], signature: vec![], scheme: 0 }],
+            outputs: values.iter().map(|&v| pay(v)).collect(),
+            metadata: None,
+        };
+        let txs = vec![Transaction::coinbase(2, vec![pay(55)], b""), spend([1; 32], &[10, 15]), spend([2; 32], &[7])];
+        let block = Block::new(2, genesis.hash(), txs, 0);
+        assert_eq!(block.total_output_value(), Ok(87));
+        assert_eq!(block.coinbase_value(), Some(55));
+
+        let no_coinbase = Block::new(2, genesis.hash(), vec![spend([1; 32], &[u64::MAX, 1])], 0);
+        assert_eq!(no_coinbase.total_output_value(), Err(Error::ValueOverflow));
+        assert_eq!(no_coinbase.coinbase_value(), None);
+    }
+
+    #[test]
+    fn test_blocks_apply() {
+        let genesis = Block::test_block(1, [0; 32]);
+        let mut ledger = Ledger::new(&genesis).unwrap();
+        assert_eq!(ledger.balance_for_pubkey_hash(&TEST_PUBKEY_HASH), 50);
+        for h in 2..=15 {
+            ledger.apply(&Block::test_block(h, ledger.tip)).unwrap();
         }
-        self
+        assert_eq!(ledger.height, 15);
+        assert_eq!(Block::test_block(2, [1; 32]), Block::test_block(2, [1; 32]));
     }
 }
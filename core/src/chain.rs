@@ -0,0 +1,283 @@
+//! Block storage and fork choice for **Obscura**.
+//!
+//! [`Blockchain`] owns every known block together with the [`Ledger`]
+//! produced by applying the canonical (longest) chain.  Canonical blocks are
+//! indexed by height and by header hash so explorers and the RPC layer
+//! (`getblock`) can look them up without replaying the chain.
+//!
+//! Blocks that do not extend the tip but link to a known block are kept as
+//! side-branch candidates.  Once a side branch becomes strictly longer than
+//! the canonical chain the node reorganises onto it, unless that would roll
+//! back more than [`crate::config::Config::max_reorg_depth`] blocks.
+//! Subscribers (see [`Blockchain::subscribe_reorgs`]) receive a
+//! [`ReorgEvent`] naming the blocks that left and joined the chain, so
+//! wallets can rescan the affected transactions.
+//!
+//! The [`UndoLog`] of each of the last `max_reorg_depth` canonical blocks is
+//! kept, so a reorg only rolls the ledger back to the fork point and applies
+//! the new branch instead of replaying from genesis.
+
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::{
+    config::Config,
+    ledger::{Ledger, UndoLog},
+    Block, BlockValidationError, Error, Hash, Result,
+};
+
+/// Sent to subscribers (see [`Blockchain::subscribe_reorgs`]) whenever fork
+/// choice switches branches.  Both lists hold block hashes in ascending
+/// height order, starting just above the fork point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReorgEvent {
+    /// Blocks removed from the canonical chain.
+    pub disconnected: Vec<Hash>,
+    /// Blocks of the new branch, ending with the new tip.
+    pub connected: Vec<Hash>,
+}
+
+/// Known blocks, canonical chain indices and the resulting ledger state.
+#[derive(Debug, Clone)]
+pub struct Blockchain {
+    pub ledger: Ledger,
+    /// Every known block, canonical or side-branch, keyed by hash.
+    blocks: HashMap<Hash, Block>,
+    /// Height index: `main[h - 1]` is the canonical block hash at height `h`.
+    main: Vec<Hash>,
+    /// Hash index mapping a canonical block hash to its height.
+    by_hash: HashMap<Hash, u64>,
+    /// Maps the txid of every canonical transaction to its block height.
+    tx_heights: HashMap<Hash, u64>,
+    /// Undo logs of the canonical blocks a reorg may still disconnect.
+    undo_logs: HashMap<Hash, UndoLog>,
+    /// Channels handed out by [`Blockchain::subscribe_reorgs`].
+    reorg_subscribers: Vec<Sender<ReorgEvent>>,
+}
+
+impl Blockchain {
+    /// Creates a chain containing only `genesis` using the default
+    /// configuration.
+    pub fn new(genesis: Block) -> Result<Self> {
+        Self::with_config(genesis, Config::default())
+    }
+
+    /// Creates a chain containing only `genesis`.
+    pub fn with_config(genesis: Block, config: Config) -> Result<Self> {
+        let ledger = Ledger::with_config(&genesis, config)?;
+        let mut chain = Blockchain {
+            ledger,
+            blocks: HashMap::new(),
+            main: Vec::new(),
+            by_hash: HashMap::new(),
+            tx_heights: HashMap::new(),
+            undo_logs: HashMap::new(),
+            reorg_subscribers: Vec::new(),
+        };
+        chain.connect(genesis);
+        Ok(chain)
+    }
+
+    /// Rebuilds a chain from `genesis` and the `blocks` stored after it, in
+    /// the order they were received.
+    ///
+    /// Stops at the first block [`Blockchain::add_block`] rejects and names
+    /// it in the returned [`BlockValidationError`].
+    pub fn replay(
+        genesis: Block,
+        blocks: impl IntoIterator<Item = Block>,
+        config: Config,
+    ) -> std::result::Result<Self, BlockValidationError> {
+        let mut chain =
+            Self::with_config(genesis.clone(), config).map_err(|e| BlockValidationError::new(&genesis, e))?;
+        for block in blocks {
+            if let Err(e) = chain.add_block(block.clone()) {
+                return Err(BlockValidationError::new(&block, e));
+            }
+        }
+        Ok(chain)
+    }
+
+    /// Writes the canonical chain, genesis first, to `path` for
+    /// [`Blockchain::load`].  Side branches are not saved.
+    #[cfg(feature = "serde")]
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let path = path.as_ref();
+        let blocks: Vec<&Block> = self.main.iter().map(|h| &self.blocks[h]).collect();
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, bincode::serialize(&blocks)?)
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .map_err(|e| Error::Storage(e.to_string()))
+    }
+
+    /// Restores a chain written by [`Blockchain::save`], revalidating every
+    /// block under `config`.
+    ///
+    /// Errors with [`Error::ConfigMismatch`] before replaying anything if the
+    /// stored genesis is not [`Config::genesis_block`], so a node restarted
+    /// with another network's or a changed consensus config refuses the data
+    /// instead of continuing a chain it would validate differently.
+    #[cfg(feature = "serde")]
+    pub fn load(path: impl AsRef<std::path::Path>, config: Config) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|e| Error::Storage(e.to_string()))?;
+        let mut blocks = bincode::deserialize::<Vec<Block>>(&bytes)?.into_iter();
+        let genesis = blocks.next().ok_or(Error::InvalidGenesis)?;
+        config.validate_against_genesis(&genesis)?;
+        Self::replay(genesis, blocks, config).map_err(|e| e.source)
+    }
+
+    /// Adds `block` to the chain.
+    ///
+    /// * A block extending the tip is validated and appended.
+    /// * A block extending any other known block is stored as a side branch;
+    ///   if that branch becomes longer than the canonical chain it is
+    ///   validated and the chain reorganises onto it.
+    ///
+    /// Errors with [`Error::ReorgTooDeep`] if the reorg would disconnect more
+    /// than `max_reorg_depth` blocks, [`Error::PrevHashMismatch`] if the
+    /// parent is unknown and [`Error::DuplicateBlock`] if the block is already
+    /// stored.
+    pub fn add_block(&mut self, block: Block) -> Result<()> {
+        let hash = block.hash();
+        if self.blocks.contains_key(&hash) {
+            return Err(Error::DuplicateBlock);
+        }
+        if Some(&block.header.prev_hash) == self.main.last() {
+            let undo = self.ledger.apply_block_with_undo(&block)?;
+            self.undo_logs.insert(hash, undo);
+            self.connect(block);
+            return Ok(());
+        }
+        if !self.blocks.contains_key(&block.header.prev_hash) {
+            return Err(Error::PrevHashMismatch);
+        }
+
+        // Walk back to the canonical chain collecting the side branch.
+        let mut branch = vec![&block];
+        let mut cursor = block.header.prev_hash;
+        while !self.by_hash.contains_key(&cursor) {
+            let parent = &self.blocks[&cursor];
+            cursor = parent.header.prev_hash;
+            branch.push(parent);
+        }
+        branch.reverse();
+        let fork_height = self.by_hash[&cursor];
+        let branch_height = fork_height + branch.len() as u64;
+
+        if branch_height <= self.height() {
+            log::debug!("stored side-branch {block}");
+            self.blocks.insert(hash, block);
+            return Ok(());
+        }
+        if self.height() - fork_height > self.ledger.config.max_reorg_depth {
+            log::warn!("refusing reorg of {} blocks to {block}", self.height() - fork_height);
+            return Err(Error::ReorgTooDeep);
+        }
+
+        // Validate the branch on a copy rolled back to the fork point before
+        // touching any state.
+        let logs: Vec<UndoLog> = self.main[fork_height as usize..]
+            .iter()
+            .map(|h| self.undo_logs.get(h).cloned().ok_or(Error::Other("missing undo log")))
+            .collect::<Result<_>>()?;
+        let mut scratch = Ledger { subscribers: Vec::new(), utxo_growth_hook: None, ..self.ledger.clone() };
+        scratch.rollback_to_height(fork_height, &logs)?;
+        for b in &branch {
+            scratch.apply_block(b)?;
+        }
+
+        // Then switch the real ledger over, so its subscribers and UTXO
+        // growth hook see the disconnected and connected blocks.
+        log::info!("reorganising {} blocks onto {block}", self.height() - fork_height);
+        self.ledger.rollback_to_height(fork_height, &logs)?;
+        let mut branch_logs = Vec::with_capacity(branch.len());
+        for b in &branch {
+            branch_logs.push(self.ledger.apply_block_with_undo(b)?);
+        }
+        let branch: Vec<Hash> = branch.iter().map(|b| b.hash()).collect();
+        let disconnected: Vec<Hash> = self.main.drain(fork_height as usize..).collect();
+        for h in &disconnected {
+            self.by_hash.remove(h);
+            self.undo_logs.remove(h);
+            for tx in &self.blocks[h].transactions {
+                self.tx_heights.remove(&tx.hash());
+            }
+        }
+        self.undo_logs.extend(branch.iter().copied().zip(branch_logs));
+        for h in &branch[..branch.len() - 1] {
+            self.push_main(*h);
+        }
+        self.connect(block);
+        let event = ReorgEvent { disconnected, connected: branch };
+        self.reorg_subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+        Ok(())
+    }
+
+    /// Returns a receiver that gets a [`ReorgEvent`] for every reorg from
+    /// now on.  Dropping the receiver unsubscribes.
+    pub fn subscribe_reorgs(&mut self) -> Receiver<ReorgEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.reorg_subscribers.push(tx);
+        rx
+    }
+
+    /// Appends an already-validated block to the canonical chain.
+    fn connect(&mut self, block: Block) {
+        let hash = block.hash();
+        self.blocks.insert(hash, block);
+        self.push_main(hash);
+    }
+
+    /// Makes the stored block `hash` the new canonical tip and indexes it,
+    /// dropping the undo log of the block that is now too deep to reorg.
+    fn push_main(&mut self, hash: Hash) {
+        self.main.push(hash);
+        let height = self.main.len() as u64;
+        let depth = usize::try_from(self.ledger.config.max_reorg_depth).unwrap_or(usize::MAX);
+        if let Some(settled) = self.main.len().checked_sub(depth.saturating_add(1)) {
+            self.undo_logs.remove(&self.main[settled]);
+        }
+        self.by_hash.insert(hash, height);
+        for tx in &self.blocks[&hash].transactions {
+            self.tx_heights.insert(tx.hash(), height);
+        }
+    }
+
+    /// Height of the chain tip (genesis == 1).
+    pub fn height(&self) -> u64 {
+        self.main.len() as u64
+    }
+
+    /// Returns the block at the chain tip.
+    pub fn tip(&self) -> Option<&Block> {
+        self.main.last().and_then(|h| self.blocks.get(h))
+    }
+
+    /// Walks the canonical chain from the tip back to genesis by following
+    /// `prev_hash` links, e.g. to page through recent blocks.
+    pub fn iter_from_tip(&self) -> impl Iterator<Item = &Block> {
+        std::iter::successors(self.tip(), |b| self.get_block_by_hash(&b.header.prev_hash))
+    }
+
+    /// Returns the canonical block at height `h`, if the chain is that long.
+    pub fn get_block_by_height(&self, h: u64) -> Option<&Block> {
+        let idx = usize::try_from(h.checked_sub(1)?).ok()?;
+        self.main.get(idx).and_then(|hash| self.blocks.get(hash))
+    }
+
+    /// Returns the canonical block whose header hashes to `hash`.
+    pub fn get_block_by_hash(&self, hash: &Hash) -> Option<&Block> {
+        self.by_hash.get(hash).and_then(|_| self.blocks.get(hash))
+    }
+
+    /// Height of the canonical block containing transaction `txid`.
+    pub fn transaction_height(&self, txid: &Hash) -> Option<u64> {
+        self.tx_heights.get(txid).copied()
+    }
+
+    /// Number of confirmations of transaction `txid`: 1 in the tip, 2 in its
+    /// parent and so on.  `None` if it is not in the canonical chain.
+    pub fn confirmations(&self, txid: &Hash) -> Option<u64> {
+        Some(self.height() - self.transaction_height(txid)? + 1)
+    }
+}
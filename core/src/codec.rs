@@ -0,0 +1,606 @@
+//! Serialisation formats for consensus data.
+//!
+//! `bincode` is convenient but is not a stable cross-language wire format.
+//! The [`Codec`] trait abstracts over the encoding so storage and the wire
+//! can move to the hand-rolled [`CanonicalCodec`] once other implementations
+//! exist.
+//!
+//! The choice is a per-network setting ([`crate::config::Config::codec`]),
+//! defaulting to [`CodecKind::Bincode`] – the historical behaviour.  It does
+//! not affect hashes: those always use the little-endian layout below.
+//!
+//! Canonical layout
+//! ----------------
+//! All integers are **big-endian**, sequences are prefixed with a `u64`
+//! length, fixed-size hashes are written raw and `Option`s carry a one-byte
+//! tag (`0` = `None`, `1` = `Some`).  Fields appear in declaration order.
+//!
+//! This is exactly `bincode`'s fixed-int layout with the byte order flipped,
+//! so the same writer in little-endian mode reproduces the [`BincodeCodec`]
+//! bytes.  `Transaction::hash` and `BlockHeader::hash` use it in every build:
+//! unlike `bincode::serialize` it cannot fail, so hashing never panics.
+//!
+//! Neither layout relies on `bincode`'s defaults, which a future major
+//! version could change (e.g. to varints): [`BincodeCodec`] spells out its
+//! options.
+
+#[cfg(feature = "serde")]
+use bincode::Options;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{hash256, Block, BlockHeader, Error, Hash, Result, Transaction, TxInput, TxOutput};
+
+/// Encoder/decoder for consensus data structures.
+pub trait Codec {
+    fn encode_transaction(&self, tx: &Transaction) -> Result<Vec<u8>>;
+    fn decode_transaction(&self, bytes: &[u8]) -> Result<Transaction>;
+    fn encode_header(&self, header: &BlockHeader) -> Result<Vec<u8>>;
+    fn decode_header(&self, bytes: &[u8]) -> Result<BlockHeader>;
+    fn encode_block(&self, block: &Block) -> Result<Vec<u8>>;
+    fn decode_block(&self, bytes: &[u8]) -> Result<Block>;
+}
+
+/// Selects the [`Codec`] used for storage and wire encoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CodecKind {
+    /// `bincode` default options (current behaviour).
+    #[default]
+    Bincode,
+    /// Hand-rolled big-endian layout, see the module docs.
+    Canonical,
+}
+
+impl CodecKind {
+    /// Returns the codec implementation for this kind.
+    pub fn codec(self) -> &'static dyn Codec {
+        match self {
+            CodecKind::Bincode => &BincodeCodec,
+            CodecKind::Canonical => &CanonicalCodec,
+        }
+    }
+
+    /// Hashes `tx` using this codec's encoding.  Only
+    /// [`CodecKind::Bincode`] agrees with [`Transaction::hash`].
+    pub fn tx_hash(self, tx: &Transaction) -> Result<Hash> {
+        Ok(hash256(&self.codec().encode_transaction(tx)?))
+    }
+
+    /// Hashes `header` using this codec's encoding.  Only
+    /// [`CodecKind::Bincode`] agrees with [`BlockHeader::hash`].
+    pub fn header_hash(self, header: &BlockHeader) -> Result<Hash> {
+        Ok(hash256(&self.codec().encode_header(header)?))
+    }
+}
+
+/// [`Codec`] backed by `bincode` default options.
+///
+/// Without the `serde` feature the same bytes are produced by the
+/// hand-rolled writer in little-endian mode.
+pub struct BincodeCodec;
+
+#[cfg(feature = "serde")]
+impl BincodeCodec {
+    /// What `bincode::serialize` uses today, pinned explicitly.
+    fn options() -> impl Options {
+        bincode::options().with_fixint_encoding().with_little_endian().allow_trailing_bytes()
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+        Ok(Self::options().serialize(value)?)
+    }
+
+    fn decode<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T> {
+        Ok(Self::options().deserialize(bytes)?)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Codec for BincodeCodec {
+    fn encode_transaction(&self, tx: &Transaction) -> Result<Vec<u8>> {
+        Self::encode(tx)
+    }
+
+    fn decode_transaction(&self, bytes: &[u8]) -> Result<Transaction> {
+        Self::decode(bytes)
+    }
+
+    fn encode_header(&self, header: &BlockHeader) -> Result<Vec<u8>> {
+        header.check_version()?;
+        Self::encode(header)
+    }
+
+    fn decode_header(&self, bytes: &[u8]) -> Result<BlockHeader> {
+        Self::decode(bytes)
+    }
+
+    fn encode_block(&self, block: &Block) -> Result<Vec<u8>> {
+        block.header.check_version()?;
+        Self::encode(block)
+    }
+
+    fn decode_block(&self, bytes: &[u8]) -> Result<Block> {
+        Self::decode(bytes)
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+impl Codec for BincodeCodec {
+    fn encode_transaction(&self, tx: &Transaction) -> Result<Vec<u8>> {
+        Ok(encode_transaction(Endian::Little, tx))
+    }
+
+    fn decode_transaction(&self, bytes: &[u8]) -> Result<Transaction> {
+        decode_transaction(Endian::Little, bytes)
+    }
+
+    fn encode_header(&self, header: &BlockHeader) -> Result<Vec<u8>> {
+        header.check_version()?;
+        Ok(encode_header(Endian::Little, header))
+    }
+
+    fn decode_header(&self, bytes: &[u8]) -> Result<BlockHeader> {
+        decode_header(Endian::Little, bytes)
+    }
+
+    fn encode_block(&self, block: &Block) -> Result<Vec<u8>> {
+        block.header.check_version()?;
+        Ok(encode_block(Endian::Little, block))
+    }
+
+    fn decode_block(&self, bytes: &[u8]) -> Result<Block> {
+        decode_block(Endian::Little, bytes)
+    }
+}
+
+/// Hand-rolled, big-endian [`Codec`] with a stable byte layout.
+pub struct CanonicalCodec;
+
+impl Codec for CanonicalCodec {
+    fn encode_transaction(&self, tx: &Transaction) -> Result<Vec<u8>> {
+        Ok(encode_transaction(Endian::Big, tx))
+    }
+
+    fn decode_transaction(&self, bytes: &[u8]) -> Result<Transaction> {
+        decode_transaction(Endian::Big, bytes)
+    }
+
+    fn encode_header(&self, header: &BlockHeader) -> Result<Vec<u8>> {
+        header.check_version()?;
+        Ok(encode_header(Endian::Big, header))
+    }
+
+    fn decode_header(&self, bytes: &[u8]) -> Result<BlockHeader> {
+        decode_header(Endian::Big, bytes)
+    }
+
+    fn encode_block(&self, block: &Block) -> Result<Vec<u8>> {
+        block.header.check_version()?;
+        Ok(encode_block(Endian::Big, block))
+    }
+
+    fn decode_block(&self, bytes: &[u8]) -> Result<Block> {
+        decode_block(Endian::Big, bytes)
+    }
+}
+
+/// Byte order of the hand-rolled layout.
+#[derive(Clone, Copy)]
+pub(crate) enum Endian {
+    /// `bincode` default layout, used for consensus hashing.
+    Little,
+    /// Canonical layout.
+    Big,
+}
+
+pub(crate) fn encode_transaction(endian: Endian, tx: &Transaction) -> Vec<u8> {
+    let mut w = Writer::new(endian);
+    w.transaction(tx);
+    w.buf
+}
+
+pub(crate) fn encode_header(endian: Endian, header: &BlockHeader) -> Vec<u8> {
+    let mut w = Writer::new(endian);
+    w.header(header);
+    w.buf
+}
+
+fn encode_block(endian: Endian, block: &Block) -> Vec<u8> {
+    let mut w = Writer::new(endian);
+    w.header(&block.header);
+    w.u64(block.transactions.len() as u64);
+    for tx in &block.transactions {
+        w.transaction(tx);
+    }
+    w.buf
+}
+
+fn decode_transaction(endian: Endian, bytes: &[u8]) -> Result<Transaction> {
+    let mut r = Reader::new(endian, bytes);
+    let tx = r.transaction()?;
+    r.finish()?;
+    Ok(tx)
+}
+
+fn decode_header(endian: Endian, bytes: &[u8]) -> Result<BlockHeader> {
+    let mut r = Reader::new(endian, bytes);
+    let header = r.header()?;
+    r.finish()?;
+    Ok(header)
+}
+
+fn decode_block(endian: Endian, bytes: &[u8]) -> Result<Block> {
+    let mut r = Reader::new(endian, bytes);
+    let header = r.header()?;
+    let count = r.u64()?;
+    let mut transactions = Vec::new();
+    for _ in 0..count {
+        transactions.push(r.transaction()?);
+    }
+    r.finish()?;
+    Ok(Block { header, transactions })
+}
+
+struct Writer {
+    endian: Endian,
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn new(endian: Endian) -> Self {
+        Self { endian, buf: Vec::new() }
+    }
+
+    fn u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    fn u16(&mut self, v: u16) {
+        match self.endian {
+            Endian::Little => self.buf.extend_from_slice(&v.to_le_bytes()),
+            Endian::Big => self.buf.extend_from_slice(&v.to_be_bytes()),
+        }
+    }
+
+    fn u32(&mut self, v: u32) {
+        match self.endian {
+            Endian::Little => self.buf.extend_from_slice(&v.to_le_bytes()),
+            Endian::Big => self.buf.extend_from_slice(&v.to_be_bytes()),
+        }
+    }
+
+    fn u64(&mut self, v: u64) {
+        match self.endian {
+            Endian::Little => self.buf.extend_from_slice(&v.to_le_bytes()),
+            Endian::Big => self.buf.extend_from_slice(&v.to_be_bytes()),
+        }
+    }
+
+    fn hash(&mut self, h: &Hash) {
+        self.buf.extend_from_slice(h);
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.u64(b.len() as u64);
+        self.buf.extend_from_slice(b);
+    }
+
+    fn transaction(&mut self, tx: &Transaction) {
+        self.u64(tx.inputs.len() as u64);
+        for inp in &tx.inputs {
+            self.hash(&inp.prev_tx);
+            self.u32(inp.output_index);
+            self.bytes(&inp.pubkey);
+            self.bytes(&inp.signature);
+            self.u8(inp.scheme);
+        }
+        self.u64(tx.outputs.len() as u64);
+        for out in &tx.outputs {
+            self.u64(out.value);
+            self.bytes(&out.pubkey_hash);
+        }
+        match &tx.metadata {
+            None => self.u8(0),
+            Some(meta) => {
+                self.u8(1);
+                self.bytes(meta);
+            }
+        }
+    }
+
+    /// Writes the layout headers had before `version` existed; only
+    /// versions other than [`crate::BLOCK_VERSION`] are appended, which
+    /// just the infallible hashing path ever writes.
+    fn header(&mut self, h: &BlockHeader) {
+        self.u64(h.index);
+        self.u64(h.timestamp);
+        self.hash(&h.prev_hash);
+        self.hash(&h.merkle_root);
+        self.u64(h.nonce);
+        self.u32(h.difficulty);
+        match &h.utxo_commitment {
+            None => self.u8(0),
+            Some(root) => {
+                self.u8(1);
+                self.hash(root);
+            }
+        }
+        if h.version != crate::BLOCK_VERSION {
+            self.u16(h.version);
+        }
+    }
+}
+
+struct Reader<'a> {
+    endian: Endian,
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(endian: Endian, buf: &'a [u8]) -> Self {
+        Self { endian, buf }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.buf.len() < n {
+            return Err(Error::Other("truncated input"));
+        }
+        let (head, tail) = self.buf.split_at(n);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    fn finish(&self) -> Result<()> {
+        if self.buf.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Other("trailing bytes"))
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let mut b = [0u8; 4];
+        b.copy_from_slice(self.take(4)?);
+        Ok(match self.endian {
+            Endian::Little => u32::from_le_bytes(b),
+            Endian::Big => u32::from_be_bytes(b),
+        })
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let mut b = [0u8; 8];
+        b.copy_from_slice(self.take(8)?);
+        Ok(match self.endian {
+            Endian::Little => u64::from_le_bytes(b),
+            Endian::Big => u64::from_be_bytes(b),
+        })
+    }
+
+    fn hash(&mut self) -> Result<Hash> {
+        let mut h = [0u8; 32];
+        h.copy_from_slice(self.take(32)?);
+        Ok(h)
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        let len = usize::try_from(self.u64()?).map_err(|_| Error::Other("length overflow"))?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn transaction(&mut self) -> Result<Transaction> {
+        let mut inputs = Vec::new();
+        for _ in 0..self.u64()? {
+            inputs.push(TxInput {
+                prev_tx: self.hash()?,
+                output_index: self.u32()?,
+                pubkey: self.bytes()?,
+                signature: self.bytes()?,
+                scheme: self.u8()?,
+            });
+        }
+        let mut outputs = Vec::new();
+        for _ in 0..self.u64()? {
+            outputs.push(TxOutput { value: self.u64()?, pubkey_hash: self.bytes()? });
+        }
+        let metadata = match self.u8()? {
+            0 => None,
+            1 => Some(self.bytes()?),
+            _ => return Err(Error::Other("invalid option tag")),
+        };
+        Ok(Transaction { inputs, outputs, metadata })
+    }
+
+    fn header(&mut self) -> Result<BlockHeader> {
+        Ok(BlockHeader {
+            version: crate::BLOCK_VERSION,
+            index: self.u64()?,
+            timestamp: self.u64()?,
+            prev_hash: self.hash()?,
+            merkle_root: self.hash()?,
+            nonce: self.u64()?,
+            difficulty: self.u32()?,
+            utxo_commitment: match self.u8()? {
+                0 => None,
+                1 => Some(self.hash()?),
+                _ => return Err(Error::Other("invalid option tag")),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_block() -> Block {
+        let tx = Transaction {
+            inputs: vec![TxInput {
+                prev_tx: [7u8; 32],
+                output_index: 1,
+                pubkey: vec![1, 2],
+                signature: vec![3],
+                scheme: 0,
+            }],
+            outputs: vec![TxOutput { value: 42, pubkey_hash: vec![9] }],
+            metadata: Some(vec![0xAB]),
+        };
+        let mut block = Block::new(2, [1u8; 32], vec![tx], 0);
+        block.header.timestamp = 1_700_000_000;
+        block
+    }
+
+    #[test]
+    fn canonical_round_trips() {
+        let block = sample_block();
+        let bytes = CanonicalCodec.encode_block(&block).unwrap();
+        let decoded = CanonicalCodec.decode_block(&bytes).unwrap();
+        assert_eq!(decoded.hash(), block.hash());
+        assert_eq!(decoded.transactions[0].hash(), block.transactions[0].hash());
+    }
+
+    #[test]
+    fn canonical_is_byte_stable() {
+        let tx = &sample_block().transactions[0];
+        let bytes = CanonicalCodec.encode_transaction(tx).unwrap();
+        assert_eq!(bytes, CanonicalCodec.encode_transaction(tx).unwrap());
+        assert_eq!(
+            hex::encode(&bytes),
+            concat!(
+                "0000000000000001",
+                "0707070707070707070707070707070707070707070707070707070707070707",
+                "00000001",
+                "00000000000000020102",
+                "000000000000000103",
+                "00",
+                "0000000000000001",
+                "000000000000002a",
+                "000000000000000109",
+                "01",
+                "0000000000000001ab"
+            )
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn bincode_options_are_pinned() {
+        let tx = &sample_block().transactions[0];
+        let bytes = BincodeCodec.encode_transaction(tx).unwrap();
+        assert_eq!(
+            hex::encode(&bytes),
+            concat!(
+                "0100000000000000",
+                "0707070707070707070707070707070707070707070707070707070707070707",
+                "01000000",
+                "02000000000000000102",
+                "010000000000000003",
+                "00",
+                "0100000000000000",
+                "2a00000000000000",
+                "010000000000000009",
+                "01",
+                "0100000000000000ab"
+            )
+        );
+    }
+
+    #[test]
+    fn canonical_rejects_truncated_input() {
+        let bytes = CanonicalCodec.encode_block(&sample_block()).unwrap();
+        assert!(CanonicalCodec.decode_block(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn little_endian_layout_matches_bincode() {
+        let block = sample_block();
+        assert_eq!(
+            encode_transaction(Endian::Little, &block.transactions[0]),
+            bincode::serialize(&block.transactions[0]).unwrap()
+        );
+        assert_eq!(encode_header(Endian::Little, &block.header), bincode::serialize(&block.header).unwrap());
+        assert_eq!(encode_block(Endian::Little, &block), bincode::serialize(&block).unwrap());
+    }
+
+    #[test]
+    fn hashing_handles_extreme_transactions() {
+        let empty = Transaction { inputs: vec![], outputs: vec![], metadata: None };
+        let big = Transaction {
+            inputs: vec![],
+            outputs: vec![TxOutput { value: u64::MAX, pubkey_hash: vec![0xFF; 1 << 16] }; 4],
+            metadata: Some(vec![0; 1 << 16]),
+        };
+        assert_ne!(empty.hash(), big.hash());
+        assert_eq!(big.hash(), hash256(&encode_transaction(Endian::Little, &big)));
+        #[cfg(feature = "serde")]
+        assert_eq!(big.hash(), hash256(&bincode::serialize(&big).unwrap()));
+    }
+
+    #[test]
+    fn encoders_reject_unknown_header_versions() {
+        let block = sample_block();
+        assert_eq!(block.header.version, crate::BLOCK_VERSION);
+        let mut future = block.clone();
+        future.header.version = 999;
+        for kind in [CodecKind::Bincode, CodecKind::Canonical] {
+            let codec = kind.codec();
+            let bytes = codec.encode_block(&block).unwrap();
+            assert_eq!(codec.decode_block(&bytes).unwrap(), block);
+            assert_eq!(codec.encode_block(&future), Err(Error::UnsupportedVersion));
+            assert_eq!(codec.encode_header(&future.header), Err(Error::UnsupportedVersion));
+        }
+        assert_ne!(future.hash(), block.hash());
+    }
+
+    #[test]
+    fn wire_header_round_trips_at_fixed_length() {
+        let mut header = sample_block().header;
+        header.nonce = u64::MAX;
+        let bytes = header.to_wire();
+        assert_eq!(bytes.len(), crate::WIRE_HEADER_LEN);
+        assert_eq!(BlockHeader::from_wire(&bytes), Ok(header.clone()));
+
+        header.utxo_commitment = Some([0xAB; 32]);
+        let with_commitment = header.to_wire();
+        assert_eq!(with_commitment.len(), bytes.len());
+        assert_eq!(BlockHeader::from_wire(&with_commitment), Ok(header.clone()));
+
+        assert!(BlockHeader::from_wire(&bytes[1..]).is_err());
+        let mut bad_tag = bytes;
+        bad_tag[94] = 2;
+        assert!(BlockHeader::from_wire(&bad_tag).is_err());
+        header.version = 999;
+        assert_eq!(BlockHeader::from_wire(&header.to_wire()), Err(Error::UnsupportedVersion));
+    }
+
+    #[test]
+    fn bincode_kind_matches_legacy_hash() {
+        let tx = &sample_block().transactions[0];
+        assert_eq!(CodecKind::Bincode.tx_hash(tx).unwrap(), tx.hash());
+        assert_ne!(CodecKind::Canonical.tx_hash(tx).unwrap(), tx.hash());
+    }
+
+    #[test]
+    fn legacy_header_round_trips_with_its_hash() {
+        // A header as written before `version` existed.
+        let legacy = [
+            &2u64.to_le_bytes()[..],
+            &1_700_000_000u64.to_le_bytes(),
+            &[1; 32],
+            &[2; 32],
+            &3u64.to_le_bytes(),
+            &4u32.to_le_bytes(),
+            &[0],
+        ]
+        .concat();
+        let header = CodecKind::Bincode.codec().decode_header(&legacy).unwrap();
+        assert_eq!((header.version, header.index, header.nonce, header.difficulty), (crate::BLOCK_VERSION, 2, 3, 4));
+        assert_eq!(CodecKind::Bincode.codec().encode_header(&header).unwrap(), legacy);
+        assert_eq!(header.hash(), hash256(&legacy));
+    }
+}
@@ -16,28 +16,227 @@
 //! assert_eq!(cfg.difficulty, 8);
 //! ```
 
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    codec::CodecKind,
+    pow::{PowAlgo, PowMode},
+    Block, BlockHeader, Error, Hash, Result, Transaction, TxOutput,
+};
+
+/// The unmined genesis header's hash plus the PoW rules it is mined under.
+type GenesisKey = (Hash, PowAlgo, PowMode);
+
+/// Winning genesis nonces, so each distinct genesis is mined once per
+/// process.
+static GENESIS_NONCES: OnceLock<Mutex<HashMap<GenesisKey, u64>>> = OnceLock::new();
+
 /// Runtime configuration shared across the crate.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Config {
-    /// PoW leading-zero difficulty in bits.
+    /// PoW difficulty of the genesis block: leading zero bits, or a compact
+    /// target under [`PowMode::Target`].  Later blocks keep their parent's
+    /// difficulty until a retarget adjusts it, see
+    /// [`crate::ledger::Ledger::next_difficulty`].
     pub difficulty: u32,
 
+    /// Lowest difficulty a block header may declare and still be accepted.
+    pub min_difficulty: u32,
+
+    /// How far, in seconds, a block timestamp may run ahead of local time.
+    pub max_future_drift: u64,
+
     /// Block subsidy in „Obsc“ paid to the miner.
     pub block_reward: u64,
 
+    /// Number of blocks after which the subsidy halves; `0` never halves.
+    pub halving_interval: u64,
+
+    /// Hard cap on [`crate::ledger::Ledger::issued`]; `None` leaves
+    /// the halving schedule as the only limit.
+    pub max_supply: Option<u64>,
+
     /// Human-readable name identifying the network (e.g. "main", "test").
     pub network: String,
+
+    /// Encoding of transactions and blocks in storage and on the RPC and
+    /// P2P wire.  Hashes do not depend on it: `Transaction::hash` and
+    /// `BlockHeader::hash` always use the little-endian layout.
+    pub codec: CodecKind,
+
+    /// Maximum number of blocks a reorg may disconnect from the canonical
+    /// chain.  Guards against long-range rewrites of deep history.
+    pub max_reorg_depth: u64,
+
+    /// Smallest value a non-coinbase transaction may assign to an output.
+    /// Zero-value (data) outputs are exempt; `0` disables the check.
+    pub dust_threshold: u64,
+
+    /// Maximum length in bytes of the miner message stamped into a coinbase.
+    pub max_coinbase_message_len: usize,
+
+    /// Proof-of-work hash function.  Fixed per network at genesis.
+    pub pow_algo: PowAlgo,
+
+    /// How header difficulty is interpreted.  Fixed per network at genesis.
+    pub pow_mode: PowMode,
+
+    /// Desired seconds between blocks, used when retargeting.
+    pub target_block_time: u64,
+
+    /// Number of blocks between difficulty retargets; `0` disables
+    /// retargeting.
+    pub retarget_interval: u64,
+
+    /// Whether the mempool lets a higher-fee transaction replace pending
+    /// ones spending the same outputs.
+    pub rbf_enabled: bool,
+
+    /// Maximum number of inputs a single transaction may carry.
+    pub max_inputs: usize,
+
+    /// Maximum number of outputs a single transaction may carry.
+    pub max_outputs: usize,
+
+    /// Maximum number of unconfirmed ancestors a transaction may have when
+    /// it enters the mempool.
+    pub max_mempool_ancestors: usize,
+
+    /// Accept inputs with an empty signature (their pubkey must still
+    /// parse).  Test networks only; never enable on a real network.
+    pub allow_unsigned: bool,
+
+    /// Timestamp of the network's genesis block, see
+    /// [`Config::genesis_block`].
+    pub genesis_timestamp: u64,
+
+    /// `(pubkey_hash, amount)` outputs the genesis coinbase pays, in order.
+    pub genesis_allocations: Vec<(Vec<u8>, u64)>,
+
+    /// Whether retargeting measures from the last block of the previous
+    /// interval instead of the first block of the current one, closing the
+    /// "time warp" attack.  See [`crate::ledger::Ledger::next_difficulty`].
+    pub time_warp_guard: bool,
+
+    /// Blocks a coinbase output must age before it can be spent, which is
+    /// also when wallets count it as spendable, see
+    /// [`crate::ledger::Ledger::spendable_balance`].
+    pub coinbase_maturity: u64,
+
+    /// Whether blocks must place each transaction before any transaction
+    /// in the same block spending its outputs, see
+    /// [`Error::UnsortedTransactions`](crate::Error::UnsortedTransactions).
+    pub enforce_tx_order: bool,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             difficulty: 8,
+            min_difficulty: 0,
+            max_future_drift: 2 * 60 * 60,
             block_reward: 50,
+            halving_interval: 210_000,
+            max_supply: None,
             network: "main".into(),
+            codec: CodecKind::default(),
+            max_reorg_depth: 100,
+            dust_threshold: 0,
+            max_coinbase_message_len: 100,
+            pow_algo: PowAlgo::default(),
+            pow_mode: PowMode::default(),
+            target_block_time: 60,
+            retarget_interval: 100,
+            rbf_enabled: true,
+            max_inputs: 1_000,
+            max_outputs: 1_000,
+            max_mempool_ancestors: 25,
+            allow_unsigned: false,
+            genesis_timestamp: 1_735_689_600,
+            genesis_allocations: Vec::new(),
+            time_warp_guard: true,
+            coinbase_maturity: 100,
+            enforce_tx_order: true,
+        }
+    }
+}
+
+impl Config {
+    /// Subsidy for the block at `height` (genesis == 1): `block_reward`
+    /// halved every `halving_interval` blocks.
+    pub fn block_subsidy(&self, height: u64) -> u64 {
+        if self.halving_interval == 0 {
+            return self.block_reward;
+        }
+        let halvings = height.saturating_sub(1) / self.halving_interval;
+        self.block_reward.checked_shr(halvings.min(64) as u32).unwrap_or(0)
+    }
+
+    /// Version byte of addresses on this network, see
+    /// [`crate::address::version_for`].
+    pub fn address_version(&self) -> u8 {
+        crate::address::version_for(&self.network)
+    }
+
+    /// Builds the genesis block this configuration implies.
+    ///
+    /// The coinbase pays `genesis_allocations` and carries the network name
+    /// as its message; the header uses `genesis_timestamp` and `difficulty`
+    /// and is mined under `pow_algo` and `pow_mode` starting from nonce 0,
+    /// so the result is deterministic.
+    ///
+    /// The winning nonce is cached, so only the first call for a given
+    /// genesis pays for mining it.
+    pub fn genesis_block(&self) -> Block {
+        let outputs = self
+            .genesis_allocations
+            .iter()
+            .map(|(pkh, value)| TxOutput { value: *value, pubkey_hash: pkh.clone() })
+            .collect();
+        let coinbase = Transaction::coinbase(1, outputs, self.network.as_bytes());
+        let transactions = vec![coinbase];
+        let header = BlockHeader {
+            version: crate::BLOCK_VERSION,
+            index: 1,
+            timestamp: self.genesis_timestamp,
+            prev_hash: [0u8; 32],
+            merkle_root: Block::calc_merkle_root(&transactions),
+            nonce: 0,
+            difficulty: self.difficulty,
+            utxo_commitment: None,
+        };
+        let key = (header.hash(), self.pow_algo, self.pow_mode);
+        let nonces = GENESIS_NONCES.get_or_init(Default::default);
+        let cached = nonces.lock().unwrap_or_else(|e| e.into_inner()).get(&key).copied();
+        if let Some(nonce) = cached {
+            return Block { header: BlockHeader { nonce, ..header }, transactions };
+        }
+        let block = Block { header, transactions }.mine_in(self.pow_algo, self.pow_mode);
+        nonces.lock().unwrap_or_else(|e| e.into_inner()).insert(key, block.header.nonce);
+        block
+    }
+
+    /// Hash of [`Config::genesis_block`].
+    pub fn genesis_hash(&self) -> Hash {
+        self.genesis_block().hash()
+    }
+
+    /// Checks that `genesis` is the block [`Config::genesis_block`] produces,
+    /// i.e. that the consensus parameters it depends on (network, genesis
+    /// timestamp and allocations, difficulty, PoW algorithm and mode) match
+    /// the chain being loaded.
+    ///
+    /// Errors with [`Error::ConfigMismatch`] otherwise.
+    pub fn validate_against_genesis(&self, genesis: &Block) -> Result<()> {
+        if genesis.hash() != self.genesis_hash() {
+            return Err(Error::ConfigMismatch);
         }
+        Ok(())
     }
 }
 
@@ -46,6 +245,12 @@ pub struct ConfigBuilder {
     inner: Config,
 }
 
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ConfigBuilder {
     /// Starts a new builder pre-populated with [`Config::default`].
     pub fn new() -> Self {
@@ -57,16 +262,126 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn min_difficulty(mut self, diff: u32) -> Self {
+        self.inner.min_difficulty = diff;
+        self
+    }
+
+    pub fn max_future_drift(mut self, secs: u64) -> Self {
+        self.inner.max_future_drift = secs;
+        self
+    }
+
     pub fn block_reward(mut self, reward: u64) -> Self {
         self.inner.block_reward = reward;
         self
     }
 
+    pub fn halving_interval(mut self, blocks: u64) -> Self {
+        self.inner.halving_interval = blocks;
+        self
+    }
+
+    pub fn max_supply(mut self, cap: u64) -> Self {
+        self.inner.max_supply = Some(cap);
+        self
+    }
+
     pub fn network<S: Into<String>>(mut self, name: S) -> Self {
         self.inner.network = name.into();
         self
     }
 
+    pub fn codec(mut self, codec: CodecKind) -> Self {
+        self.inner.codec = codec;
+        self
+    }
+
+    pub fn max_reorg_depth(mut self, depth: u64) -> Self {
+        self.inner.max_reorg_depth = depth;
+        self
+    }
+
+    pub fn dust_threshold(mut self, threshold: u64) -> Self {
+        self.inner.dust_threshold = threshold;
+        self
+    }
+
+    pub fn max_coinbase_message_len(mut self, len: usize) -> Self {
+        self.inner.max_coinbase_message_len = len;
+        self
+    }
+
+    pub fn pow_algo(mut self, algo: PowAlgo) -> Self {
+        self.inner.pow_algo = algo;
+        self
+    }
+
+    pub fn pow_mode(mut self, mode: PowMode) -> Self {
+        self.inner.pow_mode = mode;
+        self
+    }
+
+    pub fn target_block_time(mut self, secs: u64) -> Self {
+        self.inner.target_block_time = secs;
+        self
+    }
+
+    pub fn retarget_interval(mut self, blocks: u64) -> Self {
+        self.inner.retarget_interval = blocks;
+        self
+    }
+
+    pub fn rbf_enabled(mut self, enabled: bool) -> Self {
+        self.inner.rbf_enabled = enabled;
+        self
+    }
+
+    pub fn max_inputs(mut self, max: usize) -> Self {
+        self.inner.max_inputs = max;
+        self
+    }
+
+    pub fn max_outputs(mut self, max: usize) -> Self {
+        self.inner.max_outputs = max;
+        self
+    }
+
+    pub fn max_mempool_ancestors(mut self, max: usize) -> Self {
+        self.inner.max_mempool_ancestors = max;
+        self
+    }
+
+    pub fn allow_unsigned(mut self, allowed: bool) -> Self {
+        self.inner.allow_unsigned = allowed;
+        self
+    }
+
+    pub fn genesis_timestamp(mut self, ts: u64) -> Self {
+        self.inner.genesis_timestamp = ts;
+        self
+    }
+
+    pub fn genesis_allocations(mut self, allocations: Vec<(Vec<u8>, u64)>) -> Self {
+        self.inner.genesis_allocations = allocations;
+        self
+    }
+
+    pub fn time_warp_guard(mut self, enabled: bool) -> Self {
+        self.inner.time_warp_guard = enabled;
+        self
+    }
+
+    pub fn coinbase_maturity(mut self, blocks: u64) -> Self {
+        self.inner.coinbase_maturity = blocks;
+        self
+    }
+
+    pub fn enforce_tx_order(mut self, enforced: bool) -> Self {
+        self.inner.enforce_tx_order = enforced;
+        self
+    }
+
     /// Consumes the builder returning an immutable configuration value.
     pub fn finish(self) -> Config {
         self.inner
@@ -88,4 +403,62 @@ mod tests {
         assert_eq!(cfg.block_reward, 25);
         assert_eq!(cfg.network, "test");
     }
+
+    #[test]
+    fn subsidy_halves_per_interval() {
+        let cfg = ConfigBuilder::new().block_reward(50).halving_interval(10).finish();
+        assert_eq!(cfg.block_subsidy(1), 50);
+        assert_eq!(cfg.block_subsidy(10), 50);
+        assert_eq!(cfg.block_subsidy(11), 25);
+        assert_eq!(cfg.block_subsidy(21), 12);
+        assert_eq!(cfg.block_subsidy(u64::MAX), 0);
+        assert_eq!(ConfigBuilder::new().halving_interval(0).finish().block_subsidy(u64::MAX), 50);
+    }
+
+    #[test]
+    fn genesis_must_match_config() {
+        let cfg = ConfigBuilder::new().difficulty(4).network("test").finish();
+        let genesis = cfg.genesis_block();
+        assert_eq!(cfg.validate_against_genesis(&genesis), Ok(()));
+        assert!(crate::ledger::Ledger::with_config(&genesis, cfg.clone()).is_ok());
+
+        let renamed = ConfigBuilder::new().difficulty(4).network("main").finish();
+        let later = ConfigBuilder::new().difficulty(4).network("test").genesis_timestamp(1).finish();
+        let double = ConfigBuilder::new().difficulty(4).network("test").pow_algo(PowAlgo::Blake2bDouble).finish();
+        for other in [renamed, later, double] {
+            assert_eq!(other.validate_against_genesis(&genesis), Err(Error::ConfigMismatch));
+        }
+    }
+
+    #[test]
+    fn genesis_pays_configured_allocations() {
+        let cfg = ConfigBuilder::new()
+            .difficulty(4)
+            .genesis_allocations(vec![(vec![1], 1_000), (vec![2], 250)])
+            .finish();
+        let genesis = cfg.genesis_block();
+        let outputs = &genesis.transactions[0].outputs;
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[0], TxOutput { value: 1_000, pubkey_hash: vec![1] });
+        assert_eq!(outputs[1], TxOutput { value: 250, pubkey_hash: vec![2] });
+
+        let ledger = crate::ledger::Ledger::with_config(&genesis, cfg.clone()).unwrap();
+        assert_eq!(ledger.balance_for_pubkey_hash(&[1]), 1_000);
+        assert_eq!(ledger.balance_for_pubkey_hash(&[2]), 250);
+        let unfunded = ConfigBuilder::new().difficulty(4).finish();
+        assert_eq!(unfunded.validate_against_genesis(&genesis), Err(Error::ConfigMismatch));
+    }
+
+    #[test]
+    fn cached_genesis_matches_a_fresh_mine() {
+        let cfg = ConfigBuilder::new().difficulty(12).network("cache").finish();
+        let first = cfg.genesis_block();
+        assert!(first.header.nonce > 0);
+        assert_eq!(cfg.genesis_block(), first);
+        assert_eq!(cfg.genesis_hash(), first.hash());
+
+        let mut unmined = first.clone();
+        unmined.header.nonce = 0;
+        assert_eq!(unmined.mine_in(cfg.pow_algo, cfg.pow_mode), first);
+    }
 }
@@ -0,0 +1,50 @@
+//! Signature schemes accepted in transaction inputs.
+//!
+//! Every [`crate::TxInput`] carries a one-byte `scheme` tag naming the
+//! [`SigScheme`] its `pubkey` and `signature` belong to, so new schemes
+//! (Schnorr, post-quantum) can be added without changing the transaction
+//! format.  Tags are consensus rules: once assigned they never change, and
+//! inputs with an unknown tag are rejected.
+//!
+//! | Tag | Scheme  |
+//! |-----|---------|
+//! | `0` | Ed25519 |
+
+use ed25519_dalek::{PublicKey, Signature};
+
+/// Tag of [`Ed25519`], the default scheme.
+pub const ED25519: u8 = 0;
+
+/// A signature scheme transaction inputs can be signed with.
+pub trait SigScheme: Sync {
+    /// Returns `true` if `sig` is a valid signature by `pubkey` over `msg`.
+    /// Malformed keys or signatures are simply invalid.
+    fn verify(&self, pubkey: &[u8], msg: &[u8], sig: &[u8]) -> bool;
+
+    /// Returns `true` if `pubkey` parses as a key of this scheme.
+    fn is_valid_pubkey(&self, pubkey: &[u8]) -> bool;
+}
+
+/// Ed25519 with strict verification (no small-order keys, canonical `S`).
+pub struct Ed25519;
+
+impl SigScheme for Ed25519 {
+    fn verify(&self, pubkey: &[u8], msg: &[u8], sig: &[u8]) -> bool {
+        let (Ok(pk), Ok(sig)) = (PublicKey::from_bytes(pubkey), Signature::from_bytes(sig)) else {
+            return false;
+        };
+        pk.verify_strict(msg, &sig).is_ok()
+    }
+
+    fn is_valid_pubkey(&self, pubkey: &[u8]) -> bool {
+        PublicKey::from_bytes(pubkey).is_ok()
+    }
+}
+
+/// Looks up the scheme registered under `tag`.
+pub fn scheme(tag: u8) -> Option<&'static dyn SigScheme> {
+    match tag {
+        ED25519 => Some(&Ed25519),
+        _ => None,
+    }
+}
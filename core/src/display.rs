@@ -0,0 +1,68 @@
+//! One-line `Display` summaries of blocks and transactions for logs and the
+//! CLI.  `Debug` remains available when the full contents are needed.
+//!
+//! Hashes are shown as their first 8 bytes in hex.
+
+use core::fmt;
+
+use crate::{Block, Hash, Transaction};
+
+fn short_hex(hash: &Hash) -> String {
+    hex::encode(&hash[..8])
+}
+
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.outputs.iter().fold(0u64, |acc, o| acc.saturating_add(o.value));
+        write!(
+            f,
+            "tx {} (inputs: {}, outputs: {}, value: {})",
+            short_hex(&self.hash()),
+            self.inputs.len(),
+            self.outputs.len(),
+            value
+        )
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "block #{} {} (txs: {}, difficulty: {})",
+            self.header.index,
+            short_hex(&self.hash()),
+            self.transactions.len(),
+            self.header.difficulty
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Block, Transaction, TxOutput};
+
+    fn coinbase() -> Transaction {
+        let outputs = vec![
+            TxOutput { value: 30, pubkey_hash: vec![1] },
+            TxOutput { value: 20, pubkey_hash: vec![2] },
+        ];
+        Transaction::coinbase(7, outputs, b"")
+    }
+
+    #[test]
+    fn transaction_summary() {
+        let tx = coinbase();
+        let txid = hex::encode(&tx.hash()[..8]);
+        assert_eq!(tx.to_string(), format!("tx {txid} (inputs: 0, outputs: 2, value: 50)"));
+    }
+
+    #[test]
+    fn block_summary() {
+        let block = Block::new(7, [0u8; 32], vec![coinbase()], 3);
+        let s = block.to_string();
+        assert!(s.starts_with("block #7 "), "{s}");
+        assert!(s.contains(&hex::encode(&block.hash()[..8])), "{s}");
+        assert!(s.ends_with("(txs: 1, difficulty: 3)"), "{s}");
+    }
+}
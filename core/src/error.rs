@@ -29,6 +29,24 @@ pub enum Error {
     #[error("signature verification failed")]
     BadSignature,
 
+    /// A transaction input's public key is not a valid key for its scheme.
+    #[error("malformed public key")]
+    BadPubkey,
+
+    /// A transaction input's public key does not hash to the `pubkey_hash`
+    /// of the output it spends.
+    #[error("input key does not own the spent output")]
+    WrongOwner,
+
+    /// A transaction spends a coinbase output younger than
+    /// `Config::coinbase_maturity` blocks.
+    #[error("coinbase output spent before maturity")]
+    ImmatureCoinbase,
+
+    /// A transaction input names a signature scheme that does not exist.
+    #[error("unknown signature scheme")]
+    UnknownSigScheme,
+
     /// Referenced UTXO is absent from the current ledger state.
     #[error("referenced UTXO not found")]
     MissingUtxo,
@@ -49,10 +67,125 @@ pub enum Error {
     #[error("block height non-sequential")]
     NonSequentialHeight,
 
+    /// Block timestamp is further ahead of local time than
+    /// `Config::max_future_drift` allows.
+    #[error("block timestamp too far in the future")]
+    TimestampTooFarInFuture,
+
+    /// Block timestamp does not exceed the median of the previous
+    /// `MEDIAN_TIME_SPAN` blocks.
+    #[error("block timestamp not after median time past")]
+    TimestampTooEarly,
+
+    /// Header `merkle_root` does not commit to the block's transactions.
+    #[error("merkle root mismatch")]
+    MerkleMismatch,
+
     /// Block header hash does not satisfy the difficulty target.
     #[error("difficulty target not met")]
     DifficultyFail,
 
+    /// Block header declares a difficulty other than the one the chain
+    /// expects next, see `Ledger::expected_difficulty`.
+    #[error("block declares the wrong difficulty")]
+    WrongDifficulty,
+
+    /// Transaction inputs are not in canonical `(prev_tx, output_index)`
+    /// order, see `Transaction::normalize`.
+    #[error("transaction inputs not sorted")]
+    UnsortedInputs,
+
+    /// A transaction spends an output of a transaction placed at or after
+    /// it in the same block.
+    #[error("block transactions not topologically sorted")]
+    UnsortedTransactions,
+
+    /// Block carries no transactions, so it lacks the mandatory coinbase.
+    #[error("block has no coinbase transaction")]
+    MissingCoinbase,
+
+    /// Coinbase pays out more than the block subsidy plus fees.
+    #[error("coinbase exceeds block reward")]
+    ExcessiveCoinbase,
+
+    /// A coinbase would raise the total supply above `Config::max_supply`.
+    #[error("coinbase exceeds supply cap")]
+    SupplyCapExceeded,
+
+    /// A transaction output carries less value than the dust threshold.
+    #[error("output below dust threshold")]
+    DustOutput,
+
+    /// Coinbase message exceeds `Config::max_coinbase_message_len`.
+    #[error("coinbase message too long")]
+    CoinbaseMessageTooLong,
+
+    /// Transaction has more inputs than `Config::max_inputs`.
+    #[error("too many transaction inputs")]
+    TooManyInputs,
+
+    /// Transaction has more outputs than `Config::max_outputs`.
+    #[error("too many transaction outputs")]
+    TooManyOutputs,
+
+    /// Block header carries a version this release does not understand.
+    #[error("unsupported block version")]
+    UnsupportedVersion,
+
+    /// A genesis block does not match the one the configuration implies,
+    /// so the configured consensus parameters would fork from the chain.
+    #[error("config does not match genesis block")]
+    ConfigMismatch,
+
+    /// A UTXO set does not hash to the commitment it was checked against.
+    #[error("UTXO set does not match commitment")]
+    CommitmentMismatch,
+
+    /// A mempool replacement does not pay more fee than the transactions it
+    /// would evict.
+    #[error("replacement fee too low")]
+    ReplacementFeeTooLow,
+
+    /// Transaction would exceed `Config::max_mempool_ancestors` unconfirmed
+    /// ancestors.
+    #[error("too many unconfirmed ancestors")]
+    TooManyAncestors,
+
+    /// An address string is not Base58Check with a valid checksum and a
+    /// known version byte.
+    #[error("invalid address")]
+    InvalidAddress,
+
+    /// An address belongs to a different network than expected.
+    #[error("address is for a different network")]
+    WrongNetwork,
+
+    /// Available outputs cannot cover the requested amount plus fee.
+    #[error("insufficient funds")]
+    InsufficientFunds,
+
+    /// Switching to a competing branch would disconnect more blocks than
+    /// `Config::max_reorg_depth` allows.
+    #[error("reorg exceeds maximum depth")]
+    ReorgTooDeep,
+
+    /// The block is already the current tip (or otherwise already known).
+    #[error("block already applied")]
+    DuplicateBlock,
+
+    /// The genesis block breaks the genesis rules, or a block at height 1
+    /// was passed to `Ledger::apply_block`.
+    #[error("invalid genesis block")]
+    InvalidGenesis,
+
+    /// Encoding or decoding failed; carries the underlying message.
+    #[error("serialization failed: {0}")]
+    Serialization(String),
+
+    /// The UTXO storage backend failed; carries the underlying message.
+    #[error("storage failed: {0}")]
+    Storage(String),
+
     /// Placeholder for errors originating from external crates.
     #[error("{0}")]
     Other(&'static str),
@@ -60,3 +193,58 @@ pub enum Error {
 
 /// Convenient result alias used throughout the crate.
 pub type Result<T> = core::result::Result<T, Error>;
+
+#[cfg(feature = "serde")]
+impl From<bincode::Error> for Error {
+    fn from(err: bincode::Error) -> Self {
+        Error::Serialization(err.to_string())
+    }
+}
+
+/// Any Ed25519 failure (malformed signature or failed verification) means the
+/// spend is not authorised.
+impl From<ed25519_dalek::SignatureError> for Error {
+    fn from(_: ed25519_dalek::SignatureError) -> Self {
+        Error::BadSignature
+    }
+}
+
+/// An [`Error`] tagged with the block that caused it, as returned by
+/// chain-replay helpers such as [`crate::chain::Blockchain::replay`].
+#[derive(Debug, Error, PartialEq, Eq, Clone)]
+#[error("block {height} ({}) rejected: {source}", hex::encode(block_hash))]
+pub struct BlockValidationError {
+    pub block_hash: crate::Hash,
+    /// Height the block claims in its header.
+    pub height: u64,
+    pub source: Error,
+}
+
+impl BlockValidationError {
+    pub fn new(block: &crate::Block, source: Error) -> Self {
+        Self { block_hash: block.hash(), height: block.header.index, source }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Transaction;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn bincode_failure_converts_to_serialization() {
+        fn decode(bytes: &[u8]) -> Result<Transaction> {
+            Ok(bincode::deserialize(bytes)?)
+        }
+        assert!(matches!(decode(&[1, 2, 3]), Err(Error::Serialization(_))));
+    }
+
+    #[test]
+    fn signature_error_converts_to_bad_signature() {
+        fn parse(bytes: &[u8]) -> Result<ed25519_dalek::Signature> {
+            Ok(ed25519_dalek::Signature::from_bytes(bytes)?)
+        }
+        assert_eq!(parse(&[0u8; 3]), Err(Error::BadSignature));
+    }
+}
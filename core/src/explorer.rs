@@ -0,0 +1,189 @@
+//! Minimal read-only block explorer served over HTTP.
+//!
+//! Only compiled with the `http` feature.  [`Explorer::serve`] answers
+//! `GET` requests from a background thread with JSON built from a shared
+//! [`Blockchain`]:
+//!
+//! * `/block/{hash}` – header fields and txids of a canonical block.
+//! * `/tx/{txid}` – a transaction in the canonical chain and where it was
+//!   mined.
+//! * `/address/{pubkey_hash}` – confirmed balance and unspent outputs.
+//!
+//! Hashes and addresses are hex encoded.  Unknown items yield `404`,
+//! malformed paths `400`.  There is no authentication or rate limiting, so
+//! this is meant for local inspection rather than public deployment.
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, JoinHandle};
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Response, Server};
+
+use crate::{chain::Blockchain, Hash};
+
+/// A running explorer; dropping it stops the server.
+pub struct Explorer {
+    server: Arc<Server>,
+    addr: SocketAddr,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Explorer {
+    /// Binds `addr` (port `0` picks a free one) and starts answering
+    /// requests against `chain`.
+    pub fn serve(addr: impl ToSocketAddrs, chain: Arc<RwLock<Blockchain>>) -> io::Result<Self> {
+        let server = Server::http(addr).map_err(io::Error::other)?;
+        let addr = server
+            .server_addr()
+            .to_ip()
+            .ok_or_else(|| io::Error::other("explorer bound to a non-IP socket"))?;
+        let server = Arc::new(server);
+        let worker = {
+            let server = Arc::clone(&server);
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    let (status, body) = {
+                        let chain = chain.read().unwrap_or_else(|e| e.into_inner());
+                        route(&chain, request.url())
+                    };
+                    let header = Header::from_bytes("Content-Type", "application/json").expect("static header");
+                    let response = Response::from_string(body.to_string()).with_status_code(status).with_header(header);
+                    if let Err(err) = request.respond(response) {
+                        log::debug!("explorer response failed: {err}");
+                    }
+                }
+            })
+        };
+        log::info!("explorer listening on {addr}");
+        Ok(Self { server, addr, worker: Some(worker) })
+    }
+
+    /// Address the server is bound to.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for Explorer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Maps a request path to a status code and JSON body.
+fn route(chain: &Blockchain, url: &str) -> (u16, Value) {
+    let path = url.split('?').next().unwrap_or_default();
+    let mut parts = path.trim_matches('/').splitn(2, '/');
+    let found = match (parts.next(), parts.next()) {
+        (Some("block"), Some(hash)) => parse_hash(hash).map(|h| block_json(chain, &h)),
+        (Some("tx"), Some(txid)) => parse_hash(txid).map(|h| tx_json(chain, &h)),
+        (Some("address"), Some(addr)) => hex::decode(addr).ok().map(|pkh| Some(address_json(chain, &pkh))),
+        _ => return (404, json!({ "error": "not found" })),
+    };
+    match found {
+        Some(Some(body)) => (200, body),
+        Some(None) => (404, json!({ "error": "not found" })),
+        None => (400, json!({ "error": "malformed hex" })),
+    }
+}
+
+fn parse_hash(s: &str) -> Option<Hash> {
+    hex::decode(s).ok()?.try_into().ok()
+}
+
+fn block_json(chain: &Blockchain, hash: &Hash) -> Option<Value> {
+    let block = chain.get_block_by_hash(hash)?;
+    let h = &block.header;
+    Some(json!({
+        "hash": hex::encode(hash),
+        "height": h.index,
+        "prev_hash": hex::encode(h.prev_hash),
+        "merkle_root": hex::encode(h.merkle_root),
+        "timestamp": h.timestamp,
+        "nonce": h.nonce,
+        "difficulty": h.difficulty,
+        "transactions": block.transactions.iter().map(|tx| hex::encode(tx.hash())).collect::<Vec<_>>(),
+    }))
+}
+
+fn tx_json(chain: &Blockchain, txid: &Hash) -> Option<Value> {
+    let block = chain.get_block_by_height(chain.transaction_height(txid)?)?;
+    let tx = block.transactions.iter().find(|tx| tx.hash() == *txid)?;
+    Some(json!({
+        "txid": hex::encode(txid),
+        "block": hex::encode(block.hash()),
+        "height": block.header.index,
+        "confirmations": chain.confirmations(txid),
+        "inputs": tx.inputs.iter().map(|i| json!({
+            "prev_tx": hex::encode(i.prev_tx),
+            "output_index": i.output_index,
+        })).collect::<Vec<_>>(),
+        "outputs": tx.outputs.iter().map(|o| json!({
+            "value": o.value,
+            "pubkey_hash": hex::encode(&o.pubkey_hash),
+        })).collect::<Vec<_>>(),
+    }))
+}
+
+fn address_json(chain: &Blockchain, pkh: &[u8]) -> Value {
+    let utxos: Vec<Value> = chain
+        .ledger
+        .utxos_sorted()
+        .into_iter()
+        .filter(|(_, out)| out.pubkey_hash.as_slice() == pkh)
+        .map(|((txid, index), out)| json!({ "txid": hex::encode(txid), "index": index, "value": out.value }))
+        .collect();
+    json!({
+        "address": hex::encode(pkh),
+        "balance": chain.ledger.balance_for_pubkey_hash(pkh),
+        "utxos": utxos,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Block, Transaction, TxOutput};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    fn get(addr: SocketAddr, path: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+        let mut raw = String::new();
+        stream.read_to_string(&mut raw).unwrap();
+        let status = raw[9..12].parse().unwrap();
+        let body = raw.split_once("\r\n\r\n").unwrap().1;
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn serves_blocks_transactions_and_addresses() {
+        let coinbase = Transaction::coinbase(1, vec![TxOutput { value: 50, pubkey_hash: vec![0xAB] }], b"");
+        let genesis = Block::new(1, [0u8; 32], vec![coinbase.clone()], 0);
+        let hash = genesis.hash();
+        let chain = Arc::new(RwLock::new(Blockchain::new(genesis).unwrap()));
+        let explorer = Explorer::serve("127.0.0.1:0", chain).unwrap();
+        let addr = explorer.addr();
+
+        let (status, block) = get(addr, &format!("/block/{}", hex::encode(hash)));
+        assert_eq!(status, 200);
+        assert_eq!(block["height"], 1);
+        assert_eq!(block["transactions"][0], hex::encode(coinbase.hash()));
+
+        let (status, tx) = get(addr, &format!("/tx/{}", hex::encode(coinbase.hash())));
+        assert_eq!((status, tx["outputs"][0]["value"].clone()), (200, json!(50)));
+
+        let (_, address) = get(addr, "/address/ab");
+        assert_eq!(address["balance"], 50);
+
+        assert_eq!(get(addr, &format!("/block/{}", hex::encode([1u8; 32]))).0, 404);
+        assert_eq!(get(addr, "/block/zz").0, 400);
+        assert_eq!(get(addr, "/nowhere").0, 404);
+    }
+}
@@ -13,124 +13,1024 @@
 //! The API purposefully exposes only high-level operations: applying a block
 //! and querying balances.  More granular functions (e.g. mempool simulation)
 //! should be part of higher layers.
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
 
-use crate::{Hash, Transaction, TxOutput, Block, Error, Result};
-use ed25519_dalek::{PublicKey, Signature};
-use blake2::{Blake2b512, Digest};
+use crate::{
+    config::Config, hash256, merkle, now_ts, pow::{self, PowMode}, sigcache::SigCache, store::UtxoStore, validation, Block, BlockHeader, Error, Hash, Result,
+    Transaction, TxInput, TxOutput,
+};
+use rayon::prelude::*;
+#[cfg(feature = "metrics")]
+use std::time::{Duration, Instant};
 
 
 pub type UtxoKey = (Hash, u32);
 
+/// Number of most recent block timestamps whose median a new block's
+/// timestamp must exceed.
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+/// Per-phase wall-clock durations of a single [`Ledger::apply_block`] call.
+///
+/// Only available with the `metrics` feature; without it no timing code is
+/// compiled into the validation path.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationMetrics {
+    /// Parallel signature verification.
+    pub signatures: Duration,
+    /// Merkle root recomputation.
+    pub merkle: Duration,
+    /// Sequential input checks plus UTXO removal/insertion.
+    pub utxo_updates: Duration,
+    /// The whole call, including header checks.
+    pub total: Duration,
+}
+
+#[cfg(feature = "metrics")]
+type Metrics = ValidationMetrics;
+#[cfg(not(feature = "metrics"))]
+type Metrics = ();
+
+/// Evaluates `$body`, adding its duration to `$slot` when the `metrics`
+/// feature is enabled.  Without the feature `$slot` is discarded unevaluated.
+macro_rules! timed {
+    ($slot:expr, $body:expr) => {{
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let out = $body;
+        #[cfg(feature = "metrics")]
+        {
+            $slot += start.elapsed();
+        }
+        out
+    }};
+}
+
+/// UTXO-set changes between two ledger states, as returned by
+/// [`Ledger::diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LedgerDiff {
+    /// Keys present in `self` but not in the other ledger.
+    pub added: BTreeSet<UtxoKey>,
+    /// Keys present in the other ledger but not in `self`.
+    pub removed: BTreeSet<UtxoKey>,
+}
+
+/// Sent to subscribers (see [`Ledger::subscribe`]) after each successfully
+/// applied block.
+#[derive(Debug, Clone)]
+pub struct BlockEvent {
+    /// Ledger height after applying `block`.
+    pub height: u64,
+    pub block: Block,
+}
+
+/// Callback registered with [`Ledger::on_utxo_growth`].
+#[derive(Clone)]
+pub struct UtxoGrowthHook(Arc<dyn Fn(i64) + Send + Sync>);
+
+impl fmt::Debug for UtxoGrowthHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("UtxoGrowthHook(..)")
+    }
+}
+
+/// Summary of a block connected by [`Ledger::apply_block`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockApplyResult {
+    /// Sum of the fees paid by the block's non-coinbase transactions.
+    pub fees_collected: u64,
+    /// Outputs created by all of the block's transactions, including the
+    /// coinbase and any spent again within the block.
+    pub outputs_created: usize,
+}
+
+/// Size profile of the UTXO set, as returned by [`Ledger::utxo_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UtxoStats {
+    /// Number of unspent outputs.
+    pub count: usize,
+    /// Sum of their values, saturating at `u64::MAX`.
+    pub total_value: u64,
+    /// Outputs worth less than [`Config::dust_threshold`].
+    pub dust_count: usize,
+    /// Value of the largest output; `0` for an empty set.
+    pub largest: u64,
+}
+
+/// Everything needed to disconnect one block again, as returned by
+/// [`Ledger::apply_block_with_undo`].
+///
+/// Only the block's net effect is recorded: outputs both created and spent
+/// within the block appear in neither list.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UndoLog {
+    /// Height of the block this log undoes.
+    pub height: u64,
+    /// Hash of that block.
+    pub block_hash: Hash,
+    /// Tip before the block was applied.
+    pub prev_tip: Hash,
+    /// [`Ledger::tip_difficulty`] before the block was applied.
+    pub prev_difficulty: u32,
+    /// [`Ledger::issued`] before the block was applied.
+    pub prev_issued: u64,
+    /// Previously unspent outputs the block consumed.
+    pub spent: Vec<(UtxoKey, TxOutput)>,
+    /// Outputs the block created that are still unspent.
+    pub created: Vec<UtxoKey>,
+    /// Creation heights of the coinbase outputs among `spent`.
+    pub spent_coinbase_heights: Vec<(UtxoKey, u64)>,
+    /// Timestamp dropped from [`Ledger::recent_timestamps`] to make room
+    /// for the block's own.
+    pub evicted_timestamp: Option<u64>,
+}
+
+/// In-memory UTXO set type used by the default [`Ledger`].
+pub type MemoryStore = HashMap<UtxoKey, TxOutput>;
+
+/// Merkle root over `utxos`, which must be sorted by key.  Each leaf is
+/// `hash256(txid || index || value || pubkey_hash)` with big-endian integers.
+fn commitment_of<'a>(utxos: impl IntoIterator<Item = (UtxoKey, &'a TxOutput)>) -> Hash {
+    let leaves: Vec<Hash> = utxos
+        .into_iter()
+        .map(|((txid, index), out)| {
+            let mut buf = Vec::with_capacity(44 + out.pubkey_hash.len());
+            buf.extend_from_slice(&txid);
+            buf.extend_from_slice(&index.to_be_bytes());
+            buf.extend_from_slice(&out.value.to_be_bytes());
+            buf.extend_from_slice(&out.pubkey_hash);
+            hash256(&buf)
+        })
+        .collect();
+    merkle::root(&leaves)
+}
+
 #[derive(Debug, Clone)]
-/// In-memory UTXO set and chain metadata.
+/// UTXO set and chain metadata.
 ///
+/// The UTXO set lives in a [`UtxoStore`]; by default an in-memory `HashMap`.
 /// The `Ledger` is **not** thread-safe by itself; callers must wrap it in a
 /// `RwLock`/`Mutex` or use an actor model if concurrent access is required.
-pub struct Ledger {
-    pub utxos: HashMap<UtxoKey, TxOutput>,
+pub struct Ledger<S = MemoryStore> {
+    pub utxos: S,
     pub height: u64,
     pub tip: Hash,
+    /// Difficulty the tip block declared; [`Ledger::next_difficulty`]
+    /// retargets from it.
+    pub tip_difficulty: u32,
+    /// Timestamps of the last blocks, oldest first: enough for
+    /// [`Ledger::median_time_past`] and a retarget window.
+    pub recent_timestamps: VecDeque<u64>,
+    /// Consensus parameters used to validate incoming blocks.
+    pub config: Config,
+    /// Creation height of every unspent coinbase output, except those of
+    /// the genesis (pre-allocations are spendable at once) and those loaded
+    /// via [`Ledger::from_snapshot`].
+    pub coinbase_heights: HashMap<UtxoKey, u64>,
+    /// Coins issued so far: the genesis allocations plus what every later
+    /// coinbase paid beyond the fees it collected.  Coins burned afterwards
+    /// still count, so burning never frees room under
+    /// [`Config::max_supply`], which this is checked against.  Only
+    /// [`Ledger::undo_block`] lowers it.
+    pub issued: u64,
+    /// Channels handed out by [`Ledger::subscribe`].  Cloning the ledger
+    /// clones the senders, so clones notify the same receivers.
+    pub subscribers: Vec<Sender<BlockEvent>>,
+    /// Set by [`Ledger::on_utxo_growth`].  Cloning the ledger shares it.
+    pub utxo_growth_hook: Option<UtxoGrowthHook>,
+    /// Transactions whose signatures [`Ledger::validate_tx`], the mempool or
+    /// block application already verified.  Cloning the ledger shares it.
+    pub sig_cache: Arc<SigCache>,
 }
 
 impl Ledger {
-    /// Constructs a ledger initialised with the *genesis* block.
+    /// Constructs a ledger initialised with the *genesis* block using the
+    /// default configuration.
     ///
-    /// The genesis must satisfy the same validity rules as any other block
-    /// except that its `prev_hash` is all zeros and its index is 1.
+    /// See [`Ledger::apply_genesis`] for the rules the genesis must satisfy.
     pub fn new(genesis: &Block) -> Result<Self> {
-        let mut ledger = Ledger { utxos: HashMap::new(), height: 0, tip: [0u8; 32] };
-        ledger.apply_block(genesis)?;
+        Self::with_config(genesis, Config::default())
+    }
+
+    /// Constructs a ledger initialised with the *genesis* block, validating
+    /// subsequent blocks against `config`.
+    pub fn with_config(genesis: &Block, config: Config) -> Result<Self> {
+        Self::with_store(genesis, config, HashMap::new())
+    }
+
+    /// Computes the UTXO changes of `self` relative to `other`.
+    ///
+    /// Diffing the state after a block against a snapshot taken before it
+    /// yields exactly the outputs that block created (`added`) and spent
+    /// (`removed`).
+    pub fn diff(&self, other: &Ledger) -> LedgerDiff {
+        LedgerDiff {
+            added: self.utxos.keys().filter(|k| !other.utxos.contains_key(*k)).copied().collect(),
+            removed: other.utxos.keys().filter(|k| !self.utxos.contains_key(*k)).copied().collect(),
+        }
+    }
+
+    /// Returns every UTXO ordered by `(prev_tx, output_index)`, giving
+    /// explorers and state commitments an iteration order independent of
+    /// `HashMap` internals.
+    pub fn utxos_sorted(&self) -> Vec<(UtxoKey, &TxOutput)> {
+        let mut entries: Vec<(UtxoKey, &TxOutput)> = self.utxos.iter().map(|(k, v)| (*k, v)).collect();
+        entries.sort_unstable_by_key(|(k, _)| *k);
+        entries
+    }
+
+    /// Merkle root over the UTXO set in [`Ledger::utxos_sorted`] order.
+    ///
+    /// Miners may publish it in [`BlockHeader::utxo_commitment`]; nodes can
+    /// then start from a snapshot via [`Ledger::from_snapshot`] (assume-UTXO
+    /// style) instead of replaying every block since genesis.
+    pub fn commitment(&self) -> Hash {
+        commitment_of(self.utxos_sorted())
+    }
+
+    /// State root ([`Ledger::commitment`]) the ledger would have after
+    /// applying `block`, without mutating `self` or notifying subscribers.
+    ///
+    /// Useful to check a peer's claimed commitment before committing to a
+    /// block.  Validates exactly like [`Ledger::apply_block`] and returns
+    /// its error if the block is invalid.  Works on a copy of the UTXO set,
+    /// so it costs as much memory as the set itself.
+    pub fn preview_state_root(&self, block: &Block) -> Result<Hash> {
+        let mut scratch = Ledger { subscribers: Vec::new(), utxo_growth_hook: None, ..self.clone() };
+        scratch.apply_block(block)?;
+        Ok(scratch.commitment())
+    }
+
+    /// Builds a ledger at the block `tip` from a downloaded UTXO set,
+    /// trusting it only if it hashes to `commitment` (typically taken from
+    /// the tip's [`BlockHeader::utxo_commitment`]).
+    ///
+    /// The result follows `config`, which must be the network's, and starts
+    /// with an empty timestamp window, so median-time-past checks resume once
+    /// [`MEDIAN_TIME_SPAN`] further blocks are applied.  Snapshot outputs
+    /// carry no creation height and count as mature, and [`Ledger::issued`]
+    /// starts at their total as burned coins cannot be seen.  Errors with
+    /// [`Error::CommitmentMismatch`] if the set was tampered with.
+    pub fn from_snapshot(utxos: MemoryStore, tip: &BlockHeader, commitment: Hash, config: Config) -> Result<Self> {
+        Self::at_state(utxos, tip.index, tip.hash(), tip.difficulty, config)?.verified(commitment)
+    }
+
+    fn verified(self, commitment: Hash) -> Result<Self> {
+        if self.commitment() != commitment {
+            return Err(Error::CommitmentMismatch);
+        }
+        Ok(self)
+    }
+
+    /// Builds a ledger at the block `tip` from a UTXO set the caller has
+    /// already verified, e.g. against a commitment with
+    /// [`Ledger::from_snapshot`] or one obtained from a trusted source.
+    ///
+    /// The tip's timestamp seeds the timestamp window; as with
+    /// [`Ledger::from_snapshot`] the ledger follows `config` and every
+    /// output counts as mature.  Errors with [`Error::InvalidGenesis`] for
+    /// height 0.
+    pub fn from_state(utxos: MemoryStore, tip: &BlockHeader, config: Config) -> Result<Self> {
+        let mut ledger = Self::at_state(utxos, tip.index, tip.hash(), tip.difficulty, config)?;
+        ledger.recent_timestamps.push_back(tip.timestamp);
         Ok(ledger)
     }
 
+    fn at_state(utxos: MemoryStore, height: u64, tip: Hash, tip_difficulty: u32, config: Config) -> Result<Self> {
+        if height == 0 {
+            return Err(Error::InvalidGenesis);
+        }
+        // The history is unknown, so what is unspent is all that provably
+        // was issued.
+        let issued = utxos.values().fold(0, |acc: u64, out| acc.saturating_add(out.value));
+        Ok(Ledger {
+            utxos,
+            height,
+            tip,
+            tip_difficulty,
+            recent_timestamps: VecDeque::with_capacity(MEDIAN_TIME_SPAN),
+            config,
+            coinbase_heights: HashMap::new(),
+            issued,
+            subscribers: Vec::new(),
+            utxo_growth_hook: None,
+            sig_cache: Arc::default(),
+        })
+    }
+
+    /// Like [`Ledger::balance_for_pubkey_hash`] but leaving out coinbase
+    /// outputs younger than `config.coinbase_maturity` blocks: what a wallet
+    /// should show as available.
+    ///
+    /// A coinbase created at height `h` matures for the block at height
+    /// `h + coinbase_maturity`.
+    pub fn spendable_balance(&self, pkh: &[u8], config: &Config) -> u64 {
+        let next_height = self.height + 1;
+        self.utxos
+            .iter()
+            .filter(|(_, utxo)| utxo.pubkey_hash.as_slice() == pkh)
+            .filter(|(key, _)| {
+                self.coinbase_heights
+                    .get(*key)
+                    .is_none_or(|&h| next_height.saturating_sub(h) >= config.coinbase_maturity)
+            })
+            .map(|(_, u)| u.value)
+            .sum()
+    }
+
+    /// Counts and sums the UTXO set, judging dust against the ledger's
+    /// own [`Config::dust_threshold`].  Lets operators size a node's
+    /// memory.
+    pub fn utxo_stats(&self) -> UtxoStats {
+        self.utxos.values().fold(UtxoStats::default(), |stats, out| UtxoStats {
+            count: stats.count + 1,
+            total_value: stats.total_value.saturating_add(out.value),
+            dust_count: stats.dust_count + usize::from(out.value < self.config.dust_threshold),
+            largest: stats.largest.max(out.value),
+        })
+    }
+
+    pub fn balance_for_pubkey_hash(&self, pkh: &[u8]) -> u64 {
+        self.utxos
+            .values()
+            .filter(|utxo| utxo.pubkey_hash.as_slice() == pkh)
+            .map(|u| u.value)
+            .sum()
+    }
+}
+
+/// Layout version of the files [`Ledger::save_to_path`] writes, stored
+/// first so a future layout can tell old files apart.
+#[cfg(feature = "serde")]
+const SNAPSHOT_VERSION: u16 = 2;
+
+/// Contents of a snapshot file; fields are written in declaration order.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Snapshot {
+    version: u16,
+    /// Hash of the genesis the saving ledger's config implies.
+    genesis: Hash,
+    /// UTXO set sorted by key.
+    utxos: Vec<(UtxoKey, TxOutput)>,
+    height: u64,
+    tip: Hash,
+    tip_difficulty: u32,
+    recent_timestamps: Vec<u64>,
+    coinbase_heights: Vec<(UtxoKey, u64)>,
+    issued: u64,
+    /// [`Ledger::commitment`] of `utxos`.
+    commitment: Hash,
+}
+
+/// Snapshot files written by [`Ledger::save_to_path`].
+#[cfg(feature = "serde")]
+impl Ledger {
+    /// Writes the UTXO set, height, tip and its difficulty, the timestamp
+    /// window, the coinbase creation heights and [`Ledger::issued`] to
+    /// `path` so a restarted node can resume via [`Ledger::load_from_path`]
+    /// exactly where it stopped, along with the hash of the genesis
+    /// [`Ledger::config`] implies.
+    ///
+    /// The file is written next to `path` first and then renamed over it,
+    /// so an interrupted save never leaves a truncated snapshot behind.
+    pub fn save_to_path(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let path = path.as_ref();
+        let utxos: Vec<(UtxoKey, TxOutput)> = self.utxos_sorted().into_iter().map(|(k, v)| (k, v.clone())).collect();
+        let mut coinbase_heights: Vec<(UtxoKey, u64)> = self.coinbase_heights.iter().map(|(k, h)| (*k, *h)).collect();
+        coinbase_heights.sort_unstable();
+        let snapshot = Snapshot {
+            version: SNAPSHOT_VERSION,
+            genesis: self.config.genesis_hash(),
+            commitment: commitment_of(utxos.iter().map(|(k, v)| (*k, v))),
+            utxos,
+            height: self.height,
+            tip: self.tip,
+            tip_difficulty: self.tip_difficulty,
+            recent_timestamps: self.recent_timestamps.iter().copied().collect(),
+            coinbase_heights,
+            issued: self.issued,
+        };
+        let bytes = bincode::serialize(&snapshot)?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, bytes)
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .map_err(|e| Error::Storage(e.to_string()))
+    }
+
+    /// Reads a snapshot written by [`Ledger::save_to_path`].  The ledger
+    /// follows `config` and picks up median-time-past, retargeting and
+    /// coinbase maturity where the saved one left off.
+    ///
+    /// Errors with [`Error::Storage`] for a file of another layout version,
+    /// [`Error::ConfigMismatch`] if the snapshot was saved under a config
+    /// implying another genesis, see [`Config::validate_against_genesis`],
+    /// and [`Error::CommitmentMismatch`] if its UTXO set no longer matches
+    /// the commitment saved with it.
+    pub fn load_from_path(path: impl AsRef<std::path::Path>, config: Config) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|e| Error::Storage(e.to_string()))?;
+        let version: u16 = bincode::deserialize(&bytes)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(Error::Storage(format!("unsupported snapshot version {version}")));
+        }
+        let snapshot: Snapshot = bincode::deserialize(&bytes)?;
+        if snapshot.genesis != config.genesis_hash() {
+            return Err(Error::ConfigMismatch);
+        }
+        let utxos = snapshot.utxos.into_iter().collect();
+        let mut ledger = Self::at_state(utxos, snapshot.height, snapshot.tip, snapshot.tip_difficulty, config)?;
+        ledger.recent_timestamps = snapshot.recent_timestamps.into();
+        ledger.coinbase_heights = snapshot.coinbase_heights.into_iter().collect();
+        ledger.issued = snapshot.issued;
+        ledger.verified(snapshot.commitment)
+    }
+}
+
+impl<S: UtxoStore> Ledger<S> {
+    /// Constructs a ledger over `store`, which must be empty, and applies
+    /// the *genesis* block.
+    pub fn with_store(genesis: &Block, config: Config, store: S) -> Result<Self> {
+        let mut ledger = Ledger {
+            utxos: store,
+            height: 0,
+            tip: [0u8; 32],
+            tip_difficulty: 0,
+            recent_timestamps: VecDeque::with_capacity(MEDIAN_TIME_SPAN),
+            config: config.clone(),
+            coinbase_heights: HashMap::new(),
+            issued: 0,
+            subscribers: Vec::new(),
+            utxo_growth_hook: None,
+            sig_cache: Arc::default(),
+        };
+        ledger.apply_genesis(genesis, &config)?;
+        Ok(ledger)
+    }
+
+    /// Applies the genesis block to an empty ledger and adopts `config`.
+    ///
+    /// Besides the usual header and body checks the genesis must have index
+    /// 1, an all-zero `prev_hash` and carry nothing but a coinbase; errors
+    /// with [`Error::InvalidGenesis`] otherwise.
+    pub fn apply_genesis(&mut self, block: &Block, config: &Config) -> Result<()> {
+        if self.height != 0 {
+            return Err(Error::InvalidGenesis);
+        }
+        if block.header.index != 1 || block.header.prev_hash != [0u8; 32] {
+            return Err(Error::InvalidGenesis);
+        }
+        match block.transactions.as_slice() {
+            [] => return Err(Error::MissingCoinbase),
+            [coinbase] if coinbase.inputs.is_empty() => {}
+            _ => return Err(Error::InvalidGenesis),
+        }
+        self.config = config.clone();
+        self.connect_block(block, true)?;
+        log::debug!("applied genesis {block} on network {}", self.config.network);
+        Ok(())
+    }
+
     /// Validates `block` against current state and, if valid, mutates the
-    /// ledger by:
+    /// ledger by, for each transaction in order:
     /// 1. Spending each referenced input (removing UTXOs).
     /// 2. Inserting newly created outputs.
-    /// 3. Advancing `height`/`tip`.
     ///
-    /// Errors on double-spends, value overflow, signature failure or bad
-    /// linkage.
-    pub fn apply_block(&mut self, block: &Block) -> Result<()> {
-        // simple prev check
-        if block.header.index != self.height + 1 {
-            return Err(Error::NonSequentialHeight);
+    /// and finally advancing `height`/`tip`.  Because outputs are inserted
+    /// before the next transaction is checked, a transaction may spend
+    /// outputs created earlier in the same block.
+    ///
+    /// Errors on double-spends, value overflow, signature failure, bad
+    /// linkage, a Merkle root mismatch, a block without a coinbase, a
+    /// coinbase paying more than the subsidy plus fees or raising the
+    /// supply above `Config::max_supply`, or a `utxo_commitment` the
+    /// resulting UTXO set does not match.  The
+    /// genesis goes through [`Ledger::apply_genesis`] instead; passing it
+    /// here yields [`Error::InvalidGenesis`].  Re-applying the current tip
+    /// yields [`Error::DuplicateBlock`].
+    ///
+    /// Returns the fees collected and outputs created by the block.
+    pub fn apply_block(&mut self, block: &Block) -> Result<BlockApplyResult> {
+        self.apply_block_inner(block, true).map(|(_, _, result)| result)
+    }
+
+    /// Same as [`Ledger::apply_block`] but skips input signature and pubkey
+    /// checks, which dominate replay time.  Structure, linkage, proof-of-work,
+    /// output ownership and every other UTXO and value rule are still
+    /// enforced.
+    ///
+    /// **Only for blocks this node validated before**, e.g. when replaying
+    /// its own block store on restart.  Applying untrusted blocks this way
+    /// lets anyone who knows an owner's public key spend their outputs.
+    pub fn apply_block_trusted(&mut self, block: &Block) -> Result<BlockApplyResult> {
+        self.apply_block_inner(block, false).map(|(_, _, result)| result)
+    }
+
+    /// Same as [`Ledger::apply_block`] for callers that do not need the
+    /// [`BlockApplyResult`].
+    pub fn apply(&mut self, block: &Block) -> Result<()> {
+        self.apply_block(block).map(|_| ())
+    }
+
+    /// Same as [`Ledger::apply_block`] but also reports how long each
+    /// validation phase took.
+    #[cfg(feature = "metrics")]
+    pub fn apply_block_with_metrics(&mut self, block: &Block) -> Result<ValidationMetrics> {
+        self.apply_block_inner(block, true).map(|(metrics, _, _)| metrics)
+    }
+
+    /// Same as [`Ledger::apply_block`] but returns the [`UndoLog`] that
+    /// [`Ledger::undo_block`] needs to disconnect the block again.
+    pub fn apply_block_with_undo(&mut self, block: &Block) -> Result<UndoLog> {
+        self.apply_block_inner(block, true).map(|(_, undo, _)| undo)
+    }
+
+    /// Disconnects the tip block described by `log`, restoring the UTXO set,
+    /// `height`, `tip` and timestamp window to their state before it was
+    /// applied.
+    ///
+    /// Errors if `log` is not for the current tip.  The genesis cannot be
+    /// undone.
+    pub fn undo_block(&mut self, log: &UndoLog) -> Result<()> {
+        if log.height != self.height || log.block_hash != self.tip {
+            return Err(Error::Other("undo log does not match tip"));
         }
-        if block.header.prev_hash != self.tip {
-            return Err(Error::PrevHashMismatch);
+        if log.height <= 1 {
+            return Err(Error::InvalidGenesis);
         }
-        // iterate transactions
-        for (idx, tx) in block.transactions.iter().enumerate() {
-            if idx != 0 {
-                self.validate_tx(tx)?;
-            }
-            // spend
-            for inp in &tx.inputs {
-                self.utxos.remove(&(inp.prev_tx, inp.output_index));
+        for key in &log.created {
+            self.utxos.remove(key)?;
+            self.coinbase_heights.remove(key);
+        }
+        for (key, out) in &log.spent {
+            self.utxos.insert(*key, out.clone())?;
+        }
+        self.coinbase_heights.extend(log.spent_coinbase_heights.iter().copied());
+        self.recent_timestamps.pop_back();
+        if let Some(ts) = log.evicted_timestamp {
+            self.recent_timestamps.push_front(ts);
+        }
+        self.height -= 1;
+        self.tip = log.prev_tip;
+        self.tip_difficulty = log.prev_difficulty;
+        self.issued = log.prev_issued;
+        if let Some(hook) = &self.utxo_growth_hook {
+            (hook.0)(log.spent.len() as i64 - log.created.len() as i64);
+        }
+        log::debug!("disconnected block #{} {}", log.height, hex::encode(&log.block_hash[..8]));
+        Ok(())
+    }
+
+    /// Undoes blocks from the tip until `height == target`, taking each
+    /// block's log from `undo_logs` (any order).
+    ///
+    /// Every needed log is looked up, and checked to chain from the tip
+    /// down through each log's `prev_tip`, before anything changes, so a
+    /// missing or mismatched log leaves the ledger untouched.  `target` must
+    /// be between 1 and the current height.
+    pub fn rollback_to_height(&mut self, target: u64, undo_logs: &[UndoLog]) -> Result<()> {
+        if target == 0 || target > self.height {
+            return Err(Error::Other("rollback target out of range"));
+        }
+        let mut logs = Vec::with_capacity((self.height - target) as usize);
+        let mut tip = self.tip;
+        for height in (target + 1..=self.height).rev() {
+            let log = undo_logs
+                .iter()
+                .find(|l| l.height == height)
+                .ok_or(Error::Other("missing undo log"))?;
+            if log.block_hash != tip {
+                return Err(Error::Other("undo logs do not link up"));
             }
-            // create outputs
-            let tx_hash = tx.hash();
-            for (i, out) in tx.outputs.iter().enumerate() {
-                self.utxos.insert((tx_hash, i as u32), out.clone());
+            tip = log.prev_tip;
+            logs.push(log);
+        }
+        for log in logs {
+            self.undo_block(log)?;
+        }
+        Ok(())
+    }
+
+    fn apply_block_inner(&mut self, block: &Block, check_signatures: bool) -> Result<(Metrics, UndoLog, BlockApplyResult)> {
+        let result = if block.hash() == self.tip {
+            Err(Error::DuplicateBlock)
+        } else if block.header.index == 1 {
+            Err(Error::InvalidGenesis)
+        } else {
+            self.connect_block(block, check_signatures)
+        };
+        match &result {
+            Ok((_, undo, _)) => {
+                log::debug!("applied {block}");
+                self.notify(block);
+                if let Some(hook) = &self.utxo_growth_hook {
+                    (hook.0)(undo.created.len() as i64 - undo.spent.len() as i64);
+                }
             }
+            Err(err) => log::warn!("rejected {block}: {err:?}"),
+        }
+        result
+    }
+
+    /// Returns a receiver that gets a [`BlockEvent`] after every block
+    /// successfully applied from now on, e.g. so a UI can refresh balances.
+    /// Dropping the receiver unsubscribes.
+    pub fn subscribe(&mut self) -> Receiver<BlockEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Calls `hook` after every block successfully applied or undone from
+    /// now on with the net change in UTXO count, e.g. to alert operators
+    /// when the set grows unusually fast.  Replaces any previous hook.
+    pub fn on_utxo_growth(&mut self, hook: impl Fn(i64) + Send + Sync + 'static) {
+        self.utxo_growth_hook = Some(UtxoGrowthHook(Arc::new(hook)));
+    }
+
+    fn notify(&mut self, block: &Block) {
+        if self.subscribers.is_empty() {
+            return;
         }
+        let event = BlockEvent { height: self.height, block: block.clone() };
+        self.subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    /// Validates and connects `block`; shared by genesis and normal blocks.
+    /// Input signatures are only checked if `check_signatures` is set.
+    #[cfg_attr(not(feature = "metrics"), allow(clippy::let_unit_value, unused_mut))]
+    fn connect_block(&mut self, block: &Block, check_signatures: bool) -> Result<(Metrics, UndoLog, BlockApplyResult)> {
+        let mut metrics = Metrics::default();
+        let mut undo = UndoLog {
+            height: block.header.index,
+            block_hash: block.hash(),
+            prev_tip: self.tip,
+            prev_difficulty: self.tip_difficulty,
+            prev_issued: self.issued,
+            ..Default::default()
+        };
+        let mut created = BTreeSet::new();
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        self.check_header(&block.header)?;
+        self.check_body(block, check_signatures, &mut metrics)?;
+
+        let fees_collected = match self.connect_transactions(block, &mut undo, &mut created, &mut metrics) {
+            Ok(fees) => fees,
+            Err(e) => {
+                // Put back whatever the failed block already touched, so a
+                // rejection leaves no trace of it.
+                self.disconnect_outputs(&undo, &created)?;
+                self.issued = undo.prev_issued;
+                return Err(e);
+            }
+        };
         self.height = block.header.index;
         self.tip = block.hash();
-        Ok(())
+        self.tip_difficulty = block.header.difficulty;
+        if self.recent_timestamps.len() >= self.timestamp_window() {
+            undo.evicted_timestamp = self.recent_timestamps.pop_front();
+        }
+        self.recent_timestamps.push_back(block.header.timestamp);
+        #[cfg(feature = "metrics")]
+        {
+            metrics.total = start.elapsed();
+        }
+        undo.created = created.into_iter().collect();
+        let outputs_created = block.transactions.iter().map(|tx| tx.outputs.len()).sum();
+        Ok((metrics, undo, BlockApplyResult { fees_collected, outputs_created }))
     }
 
-    /// Computes the deterministic signing message for a transaction.
-    ///
-    /// We hash the serialised transaction *after* zeroing all signatures so
-    /// that each input signs the same message and the signature does not cover
-    /// itself (circular dependency).
-    fn tx_message(tx: &Transaction) -> [u8;32] {
-        let mut clone = tx.clone();
-        // Zero out signatures for hashing
-        for inp in &mut clone.inputs {
-            inp.signature.clear();
-        }
-        let encoded = bincode::serialize(&clone).expect("tx serialize");
-        let digest = blake2::Blake2b512::digest(&encoded);
-        let mut msg = [0u8;32];
-        msg.copy_from_slice(&digest[..32]);
-        msg
-    }
-
-    fn validate_tx(&self, tx: &Transaction) -> Result<()> {
-        let mut input_value = 0u64;
-        let mut output_value = 0u64;
-        for inp in &tx.inputs {
-            if let Some(prev_out) = self.utxos.get(&(inp.prev_tx, inp.output_index)) {
-                input_value += prev_out.value;
-                // Signature verification (skip if empty for placeholder)
-                if !inp.signature.is_empty() {
-                    let pk = PublicKey::from_bytes(&inp.pubkey)
-                        .map_err(|_| Error::Other("invalid pubkey"))?;
-                    let sig = Signature::from_bytes(&inp.signature)
-                        .map_err(|_| Error::Other("invalid signature"))?;
-                    let msg = Self::tx_message(tx);
-                    pk.verify_strict(&msg, &sig).map_err(|_| Error::BadSignature)?;
+    /// Spends and creates the outputs of `block`'s transactions, then checks
+    /// the coinbase reward, supply cap and UTXO commitment.
+    ///
+    /// Records every change in `undo` and `created` as it goes, so the
+    /// caller can reverse a partial application with
+    /// [`Ledger::disconnect_outputs`] when this fails.
+    fn connect_transactions(
+        &mut self,
+        block: &Block,
+        undo: &mut UndoLog,
+        created: &mut BTreeSet<UtxoKey>,
+        metrics: &mut Metrics,
+    ) -> Result<u64> {
+        #[cfg(not(feature = "metrics"))]
+        let _ = metrics;
+        let fees = timed!(metrics.utxo_updates, {
+            // iterate transactions
+            let mut fees = 0u64;
+            for (idx, tx) in block.transactions.iter().enumerate() {
+                if idx != 0 {
+                    fees = fees.checked_add(self.check_inputs(tx)?).ok_or(Error::ValueOverflow)?;
+                }
+                // spend
+                for inp in &tx.inputs {
+                    let key = (inp.prev_tx, inp.output_index);
+                    let spent = self.utxos.remove(&key)?;
+                    let coinbase_height = self.coinbase_heights.remove(&key);
+                    if !created.remove(&key) {
+                        undo.spent.extend(spent.map(|out| (key, out)));
+                        undo.spent_coinbase_heights.extend(coinbase_height.map(|h| (key, h)));
+                    }
                 }
-            } else {
-                return Err(Error::MissingUtxo);
+                // create outputs
+                let tx_hash = tx.hash();
+                for (i, out) in tx.outputs.iter().enumerate() {
+                    self.utxos.insert((tx_hash, i as u32), out.clone())?;
+                    created.insert((tx_hash, i as u32));
+                    if idx == 0 && self.height != 0 {
+                        self.coinbase_heights.insert((tx_hash, i as u32), block.header.index);
+                    }
+                }
+            }
+            // The genesis may pre-allocate arbitrary amounts.
+            if self.height != 0 {
+                self.check_coinbase_reward(&block.transactions[0], fees)?;
+            }
+            self.issued = self.issued_after(&block.transactions[0], fees)?;
+            fees
+        });
+        if let Some(expected) = block.header.utxo_commitment {
+            let mut utxos = self.utxos.iter().collect::<Result<Vec<_>>>()?;
+            utxos.sort_unstable_by_key(|(k, _)| *k);
+            if commitment_of(utxos.iter().map(|(k, v)| (*k, v))) != expected {
+                return Err(Error::CommitmentMismatch);
             }
         }
-        for out in &tx.outputs {
-            output_value += out.value;
+        Ok(fees)
+    }
+
+    /// Reverses the UTXO changes recorded so far by
+    /// [`Ledger::connect_transactions`]: drops the `created` outputs and
+    /// restores the spent ones with their coinbase heights.
+    fn disconnect_outputs(&mut self, undo: &UndoLog, created: &BTreeSet<UtxoKey>) -> Result<()> {
+        for key in created {
+            self.utxos.remove(key)?;
+            self.coinbase_heights.remove(key);
         }
-        if output_value > input_value {
-            return Err(Error::ValueOverflow);
+        for (key, out) in &undo.spent {
+            self.utxos.insert(*key, out.clone())?;
         }
+        self.coinbase_heights.extend(undo.spent_coinbase_heights.iter().copied());
         Ok(())
     }
 
-    pub fn balance_for_pubkey_hash(&self, pkh: &[u8]) -> u64 {
-        self.utxos
-            .values()
-            .filter(|utxo| utxo.pubkey_hash.as_slice() == pkh)
-            .map(|u| u.value)
-            .sum()
+    /// Median of the last [`MEDIAN_TIME_SPAN`] block timestamps, which the
+    /// next block's timestamp must exceed.
+    ///
+    /// `None` until that many blocks exist, so young chains (and tests that
+    /// build several blocks within one second) are not held to the rule.
+    pub fn median_time_past(&self) -> Option<u64> {
+        if self.recent_timestamps.len() < MEDIAN_TIME_SPAN {
+            return None;
+        }
+        let skip = self.recent_timestamps.len() - MEDIAN_TIME_SPAN;
+        let mut sorted: Vec<u64> = self.recent_timestamps.iter().skip(skip).copied().collect();
+        sorted.sort_unstable();
+        Some(sorted[MEDIAN_TIME_SPAN / 2])
+    }
+
+    /// Number of timestamps kept in [`Ledger::recent_timestamps`]: the
+    /// median-time-past span or a full retarget window, whichever is longer.
+    fn timestamp_window(&self) -> usize {
+        let interval = usize::try_from(self.config.retarget_interval).unwrap_or(usize::MAX);
+        interval.saturating_add(self.config.time_warp_guard as usize).max(MEDIAN_TIME_SPAN)
+    }
+
+    /// Cheap, context-dependent header checks: height, linkage, expected
+    /// difficulty and its floor, proof-of-work, median-time-past and
+    /// timestamp drift.
+    ///
+    /// Runs first in [`Ledger::apply_block`] so invalid blocks are rejected
+    /// before any transaction work.  Public so peers can pre-filter headers
+    /// before downloading bodies.
+    pub fn check_header(&self, header: &BlockHeader) -> Result<()> {
+        header.check_version()?;
+        if header.index != self.height + 1 {
+            return Err(Error::NonSequentialHeight);
+        }
+        if header.prev_hash != self.tip {
+            return Err(Error::PrevHashMismatch);
+        }
+        // The genesis sets the chain's starting difficulty.
+        if self.height != 0 && header.difficulty != self.expected_difficulty() {
+            return Err(Error::WrongDifficulty);
+        }
+        if self.config.pow_mode.bit_difficulty(header.difficulty) < self.config.min_difficulty
+            || !pow::header_meets_difficulty_in(header, self.config.pow_algo, self.config.pow_mode)
+        {
+            return Err(Error::DifficultyFail);
+        }
+        if self.median_time_past().is_some_and(|mtp| header.timestamp <= mtp) {
+            return Err(Error::TimestampTooEarly);
+        }
+        if header.timestamp > now_ts().saturating_add(self.config.max_future_drift) {
+            return Err(Error::TimestampTooFarInFuture);
+        }
+        Ok(())
+    }
+
+    /// Context-free body checks: coinbase presence, transaction sizes,
+    /// parent-before-child ordering if enforced, signatures and Merkle
+    /// commitment.  Input existence is checked while connecting.
+    fn check_body(&self, block: &Block, check_signatures: bool, metrics: &mut Metrics) -> Result<()> {
+        #[cfg(not(feature = "metrics"))]
+        let _ = metrics;
+        // every block must at least carry its coinbase
+        if block.transactions.is_empty() {
+            return Err(Error::MissingCoinbase);
+        }
+        for tx in &block.transactions {
+            tx.validate_structure(&self.config)?;
+        }
+        if self.config.enforce_tx_order {
+            validation::check_tx_order(&block.transactions)?;
+        }
+        // Signatures only depend on the transaction itself, so they are
+        // checked in parallel up front.  The spend phase stays sequential to
+        // catch intra-block double-spends.
+        if check_signatures {
+            let (config, cache) = (&self.config, &*self.sig_cache);
+            timed!(metrics.signatures, {
+                block.transactions[1..]
+                    .par_iter()
+                    .try_for_each(|tx| cache.verify_with(tx, || validation::verify_signatures(tx, config)))
+            })?;
+        }
+        let merkle_root = timed!(metrics.merkle, Block::calc_merkle_root(&block.transactions));
+        if merkle_root != block.header.merkle_root {
+            return Err(Error::MerkleMismatch);
+        }
+        Ok(())
+    }
+
+    /// Fully validates a non-coinbase transaction against the current UTXO
+    /// set: size limits, signatures, distinct, existing and mature inputs
+    /// and value balance.
+    ///
+    /// Delegates to [`validation::verify_transaction_cached`], so signatures
+    /// already verified by an earlier call are looked up in
+    /// [`Ledger::sig_cache`] instead.
+    pub fn validate_tx(&self, tx: &Transaction) -> Result<()> {
+        self.check_maturity(tx)?;
+        let spent = self.spent_outputs(tx)?;
+        validation::verify_transaction_cached(tx, |k| spent.get(k), &self.config, &self.sig_cache).map(|_| ())
+    }
+
+    /// Fetches the outputs `tx` spends that exist in the store.
+    fn spent_outputs(&self, tx: &Transaction) -> Result<HashMap<UtxoKey, TxOutput>> {
+        let keys: Vec<UtxoKey> = tx.inputs.iter().map(|inp| (inp.prev_tx, inp.output_index)).collect();
+        let outputs = self.utxos.get_many(&keys)?;
+        Ok(keys.into_iter().zip(outputs).filter_map(|(key, out)| Some((key, out?))).collect())
+    }
+
+    /// Input checks against the current UTXO set, see
+    /// [`validation::check_inputs`], plus coinbase maturity.  Returns the
+    /// fee.
+    fn check_inputs(&self, tx: &Transaction) -> Result<u64> {
+        self.check_maturity(tx)?;
+        let spent = self.spent_outputs(tx)?;
+        validation::check_inputs(tx, |k| spent.get(k), &self.config)
+    }
+
+    /// Rejects `tx` with [`Error::ImmatureCoinbase`] if it spends a coinbase
+    /// output that is not yet [`Config::coinbase_maturity`] blocks old at
+    /// the next height, matching [`Ledger::spendable_balance`].
+    fn check_maturity(&self, tx: &Transaction) -> Result<()> {
+        let next_height = self.height + 1;
+        let immature = |inp: &TxInput| {
+            self.coinbase_heights
+                .get(&(inp.prev_tx, inp.output_index))
+                .is_some_and(|&h| next_height.saturating_sub(h) < self.config.coinbase_maturity)
+        };
+        if tx.inputs.iter().any(immature) {
+            return Err(Error::ImmatureCoinbase);
+        }
+        Ok(())
+    }
+
+    /// Checks the coinbase pays at most the subsidy plus `fees`.
+    ///
+    /// The subsidy is taken at `self.height + 1`, the ledger's own next
+    /// height, rather than the block's self-reported index.
+    fn check_coinbase_reward(&self, coinbase: &Transaction, fees: u64) -> Result<()> {
+        let allowed = self.config.block_subsidy(self.height + 1).saturating_add(fees);
+        let paid = coinbase.total_output_value().ok_or(Error::ValueOverflow)?;
+        if paid > allowed {
+            return Err(Error::ExcessiveCoinbase);
+        }
+        Ok(())
+    }
+
+    /// [`Ledger::issued`] once `coinbase` is paid, of which `fees` were
+    /// already issued, rejected with [`Error::SupplyCapExceeded`] above
+    /// [`Config::max_supply`].  Applies to the genesis allocations too.
+    fn issued_after(&self, coinbase: &Transaction, fees: u64) -> Result<u64> {
+        let paid = coinbase.total_output_value().ok_or(Error::ValueOverflow)?;
+        let issued = self.issued.checked_add(paid.saturating_sub(fees)).ok_or(Error::ValueOverflow)?;
+        if self.config.max_supply.is_some_and(|cap| issued > cap) {
+            return Err(Error::SupplyCapExceeded);
+        }
+        Ok(issued)
+    }
+
+    /// Difficulty the next block should declare.
+    ///
+    /// Returns the tip's difficulty ([`Ledger::tip_difficulty`], or
+    /// `config.difficulty` before the genesis) except when the tip closes a
+    /// retarget interval (`height % config.retarget_interval == 0`).  There
+    /// the tip's target is rescaled by how long the blocks in
+    /// `recent_timestamps` (the last blocks' timestamps, oldest first) took
+    /// compared to `config.target_block_time` per block, see
+    /// [`pow::retarget`].  Adjustments therefore compound across intervals.
+    ///
+    /// Without `config.time_warp_guard` only the interval's own blocks are
+    /// measured, so a miner who backdates every block but the last can make
+    /// the interval look long and drive difficulty down.  With the guard the
+    /// span starts at the previous interval's last block, which must then be
+    /// the first of `retarget_interval + 1` timestamps.
+    pub fn next_difficulty(&self, config: &Config, recent_timestamps: &[u64]) -> u32 {
+        if self.height == 0 {
+            return config.difficulty;
+        }
+        let interval = config.retarget_interval;
+        if interval == 0 || !self.height.is_multiple_of(interval) || recent_timestamps.len() < 2 {
+            return self.tip_difficulty;
+        }
+        let window = usize::try_from(interval).unwrap_or(usize::MAX).saturating_add(config.time_warp_guard as usize);
+        let stamps = &recent_timestamps[recent_timestamps.len().saturating_sub(window)..];
+        let first = stamps[0];
+        let last = stamps[stamps.len() - 1];
+        let actual = last.saturating_sub(first);
+        let expected = config.target_block_time.saturating_mul(stamps.len() as u64 - 1);
+        let target = pow::retarget(&config.pow_mode.target(self.tip_difficulty), actual, expected);
+        match config.pow_mode {
+            PowMode::BitCount => pow::difficulty_of_hash(&target),
+            PowMode::Target => pow::compact_from_target(&target),
+        }
+    }
+
+    /// Difficulty the next block must declare: [`Ledger::next_difficulty`]
+    /// under the ledger's own config and timestamps.
+    pub fn expected_difficulty(&self) -> u32 {
+        let stamps: Vec<u64> = self.recent_timestamps.iter().copied().collect();
+        self.next_difficulty(&self.config, &stamps)
+    }
+
+    /// Sums the fees (inputs minus outputs) of every non-coinbase
+    /// transaction in `block`.
+    ///
+    /// Inputs are resolved against the current UTXO set plus outputs created
+    /// earlier in the same block, so this must be called while the ledger is
+    /// at the block's parent, i.e. *before* [`Ledger::apply_block`].
+    pub fn total_fees_in_block(&self, block: &Block) -> Result<u64> {
+        let mut created: HashMap<UtxoKey, u64> = HashMap::new();
+        let mut total = 0u64;
+        for (idx, tx) in block.transactions.iter().enumerate() {
+            if idx != 0 {
+                let mut input_value = 0u64;
+                for inp in &tx.inputs {
+                    let key = (inp.prev_tx, inp.output_index);
+                    let value = match self.get_output_value(&key)? {
+                        Some(value) => value,
+                        None => *created.get(&key).ok_or(Error::MissingUtxo)?,
+                    };
+                    input_value = input_value.checked_add(value).ok_or(Error::ValueOverflow)?;
+                }
+                let output_value = tx.total_output_value().ok_or(Error::ValueOverflow)?;
+                let fee = input_value.checked_sub(output_value).ok_or(Error::ValueOverflow)?;
+                total = total.checked_add(fee).ok_or(Error::ValueOverflow)?;
+            }
+            let tx_hash = tx.hash();
+            for (i, out) in tx.outputs.iter().enumerate() {
+                created.insert((tx_hash, i as u32), out.value);
+            }
+        }
+        Ok(total)
+    }
+
+    /// Value of the unspent output at `key`, or `None` if it is not in the
+    /// UTXO set.  Fallible like every other store read.
+    pub fn get_output_value(&self, key: &UtxoKey) -> Result<Option<u64>> {
+        Ok(self.utxos.get(key)?.map(|out| out.value))
+    }
+
+    /// Sums the UTXOs owned by `pkh`.  Unlike
+    /// [`Ledger::balance_for_pubkey_hash`] this works for any store and
+    /// surfaces storage errors.
+    pub fn balance(&self, pkh: &[u8]) -> Result<u64> {
+        let mut total = 0u64;
+        for entry in self.utxos.iter() {
+            let (_, out) = entry?;
+            if out.pubkey_hash.as_slice() == pkh {
+                total = total.saturating_add(out.value);
+            }
+        }
+        Ok(total)
     }
 }
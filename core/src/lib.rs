@@ -1,25 +1,107 @@
 //! Obscura Core Library
 //!
 //! Provides fundamental blockchain data structures and helpers.
+//!
+//! Building with `--no-default-features` drops the `std` feature and yields a
+//! `no_std` subset containing only [`hash256`] and the [`pow`] difficulty
+//! helpers, for embedded and WASM verifiers.
+//!
+//! The `serde` feature (on by default) derives `Serialize`/`Deserialize` for
+//! the data types.  Hashing never goes through serde: a hand-rolled encoder
+//! produces the identical bytes in every build, so hashes do not depend on
+//! features and cannot fail.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
 use blake2::{Blake2b512, Digest};
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "std")]
+pub mod address;
+#[cfg(feature = "std")]
+pub mod chain;
+#[cfg(feature = "std")]
+pub mod codec;
+#[cfg(feature = "std")]
+pub mod crypto;
+#[cfg(feature = "http")]
+pub mod explorer;
+#[cfg(feature = "std")]
 pub mod ledger;
+#[cfg(feature = "std")]
+pub mod mempool;
+#[cfg(feature = "std")]
+pub mod merkle;
 pub mod pow;
+#[cfg(feature = "std")]
+pub mod sigcache;
+#[cfg(feature = "std")]
+pub mod spv;
+#[cfg(feature = "std")]
+pub mod store;
+#[cfg(feature = "std")]
+pub mod template;
+#[cfg(feature = "std")]
+pub mod validation;
+#[cfg(feature = "std")]
 mod block_ext;
+#[cfg(feature = "std")]
+mod display;
 
 // New public modules
+#[cfg(feature = "std")]
 pub mod error;
+#[cfg(feature = "std")]
 pub mod config;
 
 // Re-export common types for convenience
-pub use error::{Error, Result};
+#[cfg(feature = "std")]
+pub use error::{BlockValidationError, Error, Result};
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+pub use block_ext::{TEST_PUBKEY, TEST_PUBKEY_HASH};
+#[cfg(feature = "std")]
 use std::time::{SystemTime, UNIX_EPOCH};
 
 pub type Hash = [u8; 32];
 
+/// The only [`BlockHeader::version`] this release accepts.
+#[cfg(feature = "std")]
+pub const BLOCK_VERSION: u16 = 1;
+
+/// Length of [`BlockHeader::to_wire`] output.
+#[cfg(feature = "std")]
+pub const WIRE_HEADER_LEN: usize = 127;
+
+#[cfg(all(feature = "std", feature = "serde"))]
+fn default_block_version() -> u16 {
+    BLOCK_VERSION
+}
+
+/// Returns the first 32 bytes of the Blake2b-512 digest of `data`.
+///
+/// This is the hash function used for transaction ids, block hashes and
+/// Merkle roots.
+pub fn hash256(data: &[u8]) -> Hash {
+    let digest = Blake2b512::digest(data);
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&digest[..32]);
+    hash
+}
+
+/// Returns `RIPEMD160(SHA256(data))`, the 20-byte hash used to derive a
+/// `pubkey_hash` (address) from a public key.
+#[cfg(feature = "std")]
+pub fn hash160(data: &[u8]) -> [u8; 20] {
+    use ripemd::Ripemd160;
+    use sha2::Sha256;
+    let mut out = [0u8; 20];
+    out.copy_from_slice(&Ripemd160::digest(Sha256::digest(data)));
+    out
+}
+
 /// Returns the UNIX timestamp in seconds.
+#[cfg(feature = "std")]
 fn now_ts() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -27,16 +109,20 @@ fn now_ts() -> u64 {
         .as_secs()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// A reference to a previous unspent transaction output (UTXO) being spent.
 ///
 /// Fields
 /// -------
 /// * `prev_tx` - 32-byte hash identifying the transaction that created the output.
 /// * `output_index` - Position of the output inside `prev_tx`’s `outputs` vector.
-/// * `pubkey` - Ed25519 public key (raw bytes) that authorises spending.
-/// * `signature` - Ed25519 signature over the deterministic transaction message (see
-///   [`ledger::Ledger::tx_message`]).
+/// * `pubkey` - Public key (raw bytes) that authorises spending.
+/// * `signature` - Signature over the deterministic transaction message (see
+///   [`Transaction::signing_message`]).
+/// * `scheme` - [`crypto`] tag of the signature scheme `pubkey` and
+///   `signature` belong to; [`crypto::ED25519`] by default.
 ///
 /// The signature must validate against `pubkey` and authorises the spend if the
 /// referenced UTXO’s `pubkey_hash` matches `hash160(pubkey)` once address
@@ -46,9 +132,30 @@ pub struct TxInput {
     pub output_index: u32,
     pub pubkey: Vec<u8>,
     pub signature: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub scheme: u8,
+}
+
+#[cfg(feature = "std")]
+impl TxInput {
+    /// Builds an Ed25519 input, rejecting a `pubkey` that is not a 32-byte
+    /// key and a `signature` that is neither empty (not yet signed) nor 64
+    /// bytes.  Whether the signature actually verifies is left to
+    /// validation.
+    pub fn new(prev_tx: Hash, output_index: u32, pubkey: Vec<u8>, signature: Vec<u8>) -> Result<Self> {
+        if pubkey.len() != ed25519_dalek::PUBLIC_KEY_LENGTH {
+            return Err(Error::Other("invalid pubkey length"));
+        }
+        if !signature.is_empty() && signature.len() != ed25519_dalek::SIGNATURE_LENGTH {
+            return Err(Error::Other("invalid signature length"));
+        }
+        Ok(Self { prev_tx, output_index, pubkey, signature, scheme: crypto::ED25519 })
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// A newly created spendable output produced by a transaction.
 ///
 /// Fields
@@ -63,7 +170,21 @@ pub struct TxOutput {
     pub pubkey_hash: Vec<u8>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg(feature = "std")]
+impl TxOutput {
+    /// Builds an output, rejecting an empty `pubkey_hash` (which nobody
+    /// could ever spend).
+    pub fn new(value: u64, pubkey_hash: Vec<u8>) -> Result<Self> {
+        if pubkey_hash.is_empty() {
+            return Err(Error::Other("empty pubkey_hash"));
+        }
+        Ok(Self { value, pubkey_hash })
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// Represents a transfer of value.
 ///
 /// A transaction destroys the UTXOs referenced by all [`TxInput`]s and creates
@@ -81,17 +202,154 @@ pub struct Transaction {
     pub metadata: Option<Vec<u8>>, // Optional extra data
 }
 
+#[cfg(feature = "std")]
 impl Transaction {
+    /// Builds a coinbase transaction for the block at `height`.
+    ///
+    /// `metadata` holds the big-endian height (which also keeps coinbase
+    /// txids unique across blocks), a big-endian extra-nonce starting at 0
+    /// (see [`Block::set_extra_nonce`]) and an optional free-form `message`
+    /// stamped by the miner.  Consensus never interprets the message; the
+    /// length cap is enforced by the template builder.
+    pub fn coinbase(height: u64, outputs: Vec<TxOutput>, message: &[u8]) -> Self {
+        let mut metadata = height.to_be_bytes().to_vec();
+        metadata.extend_from_slice(&0u64.to_be_bytes());
+        metadata.extend_from_slice(message);
+        Self { inputs: vec![], outputs, metadata: Some(metadata) }
+    }
+
+    /// Returns the miner message of a coinbase built by
+    /// [`Transaction::coinbase`], if any.
+    pub fn coinbase_message(&self) -> Option<&[u8]> {
+        match &self.metadata {
+            Some(meta) if self.inputs.is_empty() && meta.len() > 16 => Some(&meta[16..]),
+            _ => None,
+        }
+    }
+
+    /// Returns the extra-nonce of a coinbase built by
+    /// [`Transaction::coinbase`].
+    pub fn coinbase_extra_nonce(&self) -> Option<u64> {
+        match &self.metadata {
+            Some(meta) if self.inputs.is_empty() && meta.len() >= 16 => {
+                Some(u64::from_be_bytes(meta[8..16].try_into().expect("8 bytes")))
+            }
+            _ => None,
+        }
+    }
+
+    /// Sorts inputs by `(prev_tx, output_index)` so that equal spends have a
+    /// single canonical encoding.
+    ///
+    /// Outputs are deliberately left in caller order: their position is part
+    /// of the UTXO key and wallets rely on it to tell payment from change.
+    ///
+    /// Signatures commit to input order, so this must be called *before*
+    /// signing.
+    pub fn normalize(&mut self) {
+        self.inputs.sort_by_key(|inp| (inp.prev_tx, inp.output_index));
+    }
+
+    /// Sum of the output values; `None` if it overflows `u64`.
+    pub fn total_output_value(&self) -> Option<u64> {
+        self.outputs.iter().try_fold(0u64, |acc, o| acc.checked_add(o.value))
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn output_count(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Returns `true` if inputs are in the order produced by
+    /// [`Transaction::normalize`].
+    pub fn is_normalized(&self) -> bool {
+        self.inputs
+            .windows(2)
+            .all(|w| (w[0].prev_tx, w[0].output_index) <= (w[1].prev_tx, w[1].output_index))
+    }
+
+    /// Errors with [`Error::DoubleSpend`] if two inputs spend the same
+    /// `(prev_tx, output_index)` outpoint.
+    pub fn verify_no_duplicate_inputs(&self) -> Result<()> {
+        let mut outpoints: Vec<(Hash, u32)> = self.inputs.iter().map(|inp| (inp.prev_tx, inp.output_index)).collect();
+        outpoints.sort_unstable();
+        if outpoints.windows(2).any(|w| w[0] == w[1]) {
+            return Err(Error::DoubleSpend);
+        }
+        Ok(())
+    }
+
+    /// Context-free size checks: at most `config.max_inputs` inputs and
+    /// `config.max_outputs` outputs, bounding per-transaction validation
+    /// work.
+    pub fn validate_structure(&self, config: &config::Config) -> Result<()> {
+        if self.inputs.len() > config.max_inputs {
+            return Err(Error::TooManyInputs);
+        }
+        if self.outputs.len() > config.max_outputs {
+            return Err(Error::TooManyOutputs);
+        }
+        Ok(())
+    }
+
+    /// Returns the txid: the hash of the `bincode` encoding.
+    ///
+    /// The bytes come from the infallible hand-rolled writer in [`codec`]
+    /// rather than `bincode` itself, so hashing cannot fail or panic on any
+    /// input.
     pub fn hash(&self) -> Hash {
-        let encoded = bincode::serialize(self).expect("tx serialize");
-        let digest = Blake2b512::digest(&encoded);
-        let mut hash = [0u8; 32];
-        hash.copy_from_slice(&digest[..32]);
-        hash
+        hash256(&codec::encode_transaction(codec::Endian::Little, self))
+    }
+
+    /// Computes the deterministic message every input signs.
+    ///
+    /// We hash the serialised transaction *after* zeroing all signatures so
+    /// that each input signs the same message and the signature does not cover
+    /// itself (circular dependency).
+    pub fn signing_message(&self) -> Hash {
+        let mut clone = self.clone();
+        for inp in &mut clone.inputs {
+            inp.signature.clear();
+        }
+        clone.hash()
+    }
+
+    /// Verifies every input against the output it spends, without a ledger:
+    /// `prev_outputs[i]` must be the output `inputs[i]` references.
+    ///
+    /// Each input's `pubkey` must hash ([`hash160`]) to the output's
+    /// `pubkey_hash` and its signature must verify over
+    /// [`Transaction::signing_message`] under the input's scheme.  Missing
+    /// signatures are an error even where `Config::allow_unsigned` would
+    /// accept them.  Intended for signing services and hardware wallets that
+    /// only hold the outputs being spent.
+    pub fn verify_signatures(&self, prev_outputs: &[TxOutput]) -> Result<()> {
+        if prev_outputs.len() != self.inputs.len() {
+            return Err(Error::Other("prev_outputs do not match inputs"));
+        }
+        let msg = self.signing_message();
+        for (inp, prev) in self.inputs.iter().zip(prev_outputs) {
+            if hash160(&inp.pubkey).as_slice() != prev.pubkey_hash.as_slice() {
+                return Err(Error::Other("pubkey does not match output"));
+            }
+            let scheme = crypto::scheme(inp.scheme).ok_or(Error::UnknownSigScheme)?;
+            if !scheme.is_valid_pubkey(&inp.pubkey) {
+                return Err(Error::BadPubkey);
+            }
+            if !scheme.verify(&inp.pubkey, &msg, &inp.signature) {
+                return Err(Error::BadSignature);
+            }
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// Metadata identifying a block.
 ///
 /// The header is the portion hashed during proof-of-work.  Changing *any* field
@@ -99,25 +357,114 @@ impl Transaction {
 ///
 /// Fields
 /// -------
+/// * `version` – Block format version, currently [`BLOCK_VERSION`].  Blocks
+///   with any other version are rejected with
+///   [`Error::UnsupportedVersion`].  Only [`BlockHeader::to_wire`] carries
+///   it: the hashed layout, the [`codec`] layouts and serde formats leave
+///   version 1 out, so headers written before the field existed still
+///   decode and keep their hashes.
 /// * `index` – Height of the block (genesis == 1).
 /// * `timestamp` – Seconds since Unix epoch.
 /// * `prev_hash` – Hash of the previous block’s header (all zeros for genesis).
-/// * `merkle_root` – Root hash of the merkle tree built from transaction
-///   hashes.  Currently implemented as a simple concatenation hash for
-///   scaffolding.
+/// * `merkle_root` – Root hash of the binary merkle tree built from
+///   transaction hashes (see [`merkle`]).
 /// * `nonce` – Incremented during mining until the header hash satisfies the
 ///   target difficulty.
 /// * `difficulty` – Target leading-zero bit count the hash must satisfy.
+/// * `utxo_commitment` – Optional [`ledger::Ledger::commitment`] of the UTXO
+///   set *after* this block, letting new nodes start from a verified
+///   snapshot instead of replaying history.
 pub struct BlockHeader {
+    #[cfg_attr(feature = "serde", serde(skip, default = "default_block_version"))]
+    pub version: u16,
     pub index: u64,
     pub timestamp: u64,
     pub prev_hash: Hash,
     pub merkle_root: Hash,
     pub nonce: u64,
     pub difficulty: u32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub utxo_commitment: Option<Hash>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg(feature = "std")]
+impl BlockHeader {
+    /// Returns the Blake2b-256 hash of the header, i.e. the block hash.
+    /// Infallible, like [`Transaction::hash`].
+    ///
+    /// Version 1 headers hash exactly as they did before the `version`
+    /// field existed; any other version is appended to the hashed bytes.
+    pub fn hash(&self) -> Hash {
+        hash256(&codec::encode_header(codec::Endian::Little, self))
+    }
+
+    /// Errors with [`Error::UnsupportedVersion`] unless `version` is
+    /// [`BLOCK_VERSION`].
+    pub fn check_version(&self) -> Result<()> {
+        if self.version != BLOCK_VERSION {
+            return Err(Error::UnsupportedVersion);
+        }
+        Ok(())
+    }
+
+    /// Returns the hash that must meet the difficulty target under `algo`.
+    pub fn pow_hash(&self, algo: pow::PowAlgo) -> Hash {
+        algo.finish(self.hash())
+    }
+
+    /// Fixed-size P2P encoding: `version` (u16), `index`, `timestamp`
+    /// (u64), `prev_hash`, `merkle_root`, `nonce` (u64), `difficulty` (u32,
+    /// compact bits under [`pow::PowMode::Target`]), then a presence byte
+    /// and 32 bytes for `utxo_commitment` (zeros when absent).  Integers
+    /// are big-endian.
+    pub fn to_wire(&self) -> [u8; WIRE_HEADER_LEN] {
+        let mut out = [0u8; WIRE_HEADER_LEN];
+        out[0..2].copy_from_slice(&self.version.to_be_bytes());
+        out[2..10].copy_from_slice(&self.index.to_be_bytes());
+        out[10..18].copy_from_slice(&self.timestamp.to_be_bytes());
+        out[18..50].copy_from_slice(&self.prev_hash);
+        out[50..82].copy_from_slice(&self.merkle_root);
+        out[82..90].copy_from_slice(&self.nonce.to_be_bytes());
+        out[90..94].copy_from_slice(&self.difficulty.to_be_bytes());
+        if let Some(commitment) = self.utxo_commitment {
+            out[94] = 1;
+            out[95..].copy_from_slice(&commitment);
+        }
+        out
+    }
+
+    /// Decodes [`BlockHeader::to_wire`] output.
+    ///
+    /// Errors unless `bytes` is exactly [`WIRE_HEADER_LEN`] long with a
+    /// canonical commitment slot, and with [`Error::UnsupportedVersion`]
+    /// for unknown versions.
+    pub fn from_wire(bytes: &[u8]) -> Result<BlockHeader> {
+        let bytes: &[u8; WIRE_HEADER_LEN] = bytes.try_into().map_err(|_| Error::Other("invalid header length"))?;
+        let hash = |range: core::ops::Range<usize>| -> Hash { bytes[range].try_into().expect("32 bytes") };
+        let u64_at = |at: usize| u64::from_be_bytes(bytes[at..at + 8].try_into().expect("8 bytes"));
+        let utxo_commitment = match bytes[94] {
+            0 if bytes[95..].iter().all(|&b| b == 0) => None,
+            1 => Some(hash(95..127)),
+            _ => return Err(Error::Other("invalid option tag")),
+        };
+        let header = BlockHeader {
+            version: u16::from_be_bytes([bytes[0], bytes[1]]),
+            index: u64_at(2),
+            timestamp: u64_at(10),
+            prev_hash: hash(18..50),
+            merkle_root: hash(50..82),
+            nonce: u64_at(82),
+            difficulty: u32::from_be_bytes(bytes[90..94].try_into().expect("4 bytes")),
+            utxo_commitment,
+        };
+        header.check_version()?;
+        Ok(header)
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// A container for an ordered set of transactions plus a header linking it
 /// into the blockchain.
 ///
@@ -129,6 +476,7 @@ pub struct Block {
     pub transactions: Vec<Transaction>,
 }
 
+#[cfg(feature = "std")]
 impl Block {
         /// Constructs a new block with a freshly calculated merkle root and the
     /// current wall-clock timestamp.  The `nonce` starts at 0; callers should
@@ -136,14 +484,31 @@ impl Block {
     /// method) to find a valid nonce.
     pub fn new(index: u64, prev_hash: Hash, transactions: Vec<Transaction>, difficulty: u32) -> Self {
         let merkle_root = Self::calc_merkle_root(&transactions);
+        Self::new_with_root(index, prev_hash, transactions, difficulty, merkle_root, now_ts())
+    }
+
+    /// Same as [`Block::new`] but trusts the caller's `merkle_root` and
+    /// `timestamp` instead of computing them, e.g. when a template already
+    /// hashed the transactions.  A wrong root is caught by
+    /// [`Block::is_valid`] and [`crate::ledger::Ledger::apply_block`].
+    pub fn new_with_root(
+        index: u64,
+        prev_hash: Hash,
+        transactions: Vec<Transaction>,
+        difficulty: u32,
+        merkle_root: Hash,
+        timestamp: u64,
+    ) -> Self {
         Self {
             header: BlockHeader {
+                version: BLOCK_VERSION,
                 index,
-                timestamp: now_ts(),
+                timestamp,
                 prev_hash,
                 merkle_root,
                 nonce: 0,
                 difficulty,
+                utxo_commitment: None,
             },
             transactions,
         }
@@ -151,19 +516,28 @@ impl Block {
 
         /// Computes the Merkle root of `txs`.
     ///
-    /// NOTE: This is currently a **placeholder** implementation that simply
-    /// hashes the concatenation of transaction hashes.  For production use we
-    /// will replace it with a proper binary Merkle tree with duplicate handling
-    /// (Bitcoin-style) which enables efficient SPV proofs.
+    /// Uses a binary Merkle tree over the transaction hashes, duplicating the
+    /// last node of odd levels (Bitcoin-style), so inclusion can be proven
+    /// with [`Block::merkle_proof`].
     pub fn calc_merkle_root(txs: &[Transaction]) -> Hash {
-        let mut hasher = Blake2b512::new();
-        for tx in txs {
-            hasher.update(tx.hash());
-        }
-        let result = hasher.finalize();
-        let mut root = [0u8; 32];
-        root.copy_from_slice(&result[..32]);
-        root
+        let leaves: Vec<Hash> = txs.iter().map(Transaction::hash).collect();
+        merkle::root(&leaves)
+    }
+
+    /// Computes a Merkle root from transaction hashes as they are produced,
+    /// without collecting them first; see [`merkle::root_from`].  Yields
+    /// the same root as [`Block::calc_merkle_root`] for the same
+    /// transactions.
+    pub fn merkle_root_from<I: Iterator<Item = Hash>>(hashes: I) -> Hash {
+        merkle::root_from(hashes)
+    }
+
+    /// Returns the Merkle inclusion proof for the transaction at `index`,
+    /// checkable against `header.merkle_root` with
+    /// [`merkle::verify_proof`].
+    pub fn merkle_proof(&self, index: usize) -> Option<Vec<(Hash, bool)>> {
+        let leaves: Vec<Hash> = self.transactions.iter().map(Transaction::hash).collect();
+        merkle::proof(&leaves, index)
     }
 
         /// Returns the Blake2b-256 hash of the block header.
@@ -171,10 +545,6 @@ impl Block {
     /// This hash functions as both the block identifier and the proof-of-work
     /// input.
     pub fn hash(&self) -> Hash {
-        let encoded = bincode::serialize(&self.header).expect("header serialize");
-        let digest = Blake2b512::digest(&encoded);
-        let mut h = [0u8; 32];
-        h.copy_from_slice(&digest[..32]);
-        h
+        self.header.hash()
     }
 }
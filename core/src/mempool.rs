@@ -0,0 +1,392 @@
+//! Pool of validated transactions waiting to be mined.
+//!
+//! Transactions are validated against the ledger on entry and indexed by
+//! txid.  Each entry remembers the outputs it spends so wallets can account
+//! for pending spends without consulting the ledger again.
+//!
+//! Transactions may spend outputs of other pooled transactions.  Such
+//! chains are capped by `Config::max_mempool_ancestors` so a long tail of
+//! low-fee descendants cannot pin a parent in the pool.
+//!
+//! Conflicting spends are resolved by replace-by-fee when
+//! `Config::rbf_enabled` is set: the newcomer must pay strictly more than
+//! everything it evicts, descendants of the replaced transactions included.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{
+    codec::{self, Endian},
+    ledger::{Ledger, UtxoKey},
+    validation, Error, Hash, Result, Transaction, TxOutput,
+};
+
+/// A pooled transaction together with data derived when it was admitted.
+#[derive(Debug, Clone)]
+pub struct MempoolEntry {
+    pub tx: Transaction,
+    /// Input value minus output value.
+    pub fee: u64,
+    /// The outputs this transaction spends, confirmed or pooled, in input
+    /// order.
+    pub spent: Vec<TxOutput>,
+}
+
+/// Unconfirmed transactions keyed by txid.
+#[derive(Debug, Clone, Default)]
+pub struct Mempool {
+    entries: HashMap<Hash, MempoolEntry>,
+    /// Which pooled transaction spends each outpoint.
+    spent_by: HashMap<UtxoKey, Hash>,
+}
+
+impl Mempool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Validates `tx` against `ledger` and the pool and adds it, returning
+    /// its txid.  Inputs may spend confirmed outputs or outputs of pooled
+    /// transactions.
+    ///
+    /// If an input is already spent by pooled transactions, `tx` replaces
+    /// them and their descendants (replace-by-fee) provided
+    /// `ledger.config.rbf_enabled` is set and its fee is strictly higher than
+    /// their combined fees.  Otherwise errors with [`Error::DoubleSpend`] or
+    /// [`Error::ReplacementFeeTooLow`].  Errors with
+    /// [`Error::TooManyAncestors`] if `tx` would have more than
+    /// `ledger.config.max_mempool_ancestors` pooled ancestors.
+    pub fn add(&mut self, tx: Transaction, ledger: &Ledger) -> Result<Hash> {
+        let lookup = |k: &UtxoKey| ledger.utxos.get(k).or_else(|| self.pooled_output(k));
+        let fee = validation::verify_transaction_cached(&tx, lookup, &ledger.config, &ledger.sig_cache)?;
+        let spent: Vec<TxOutput> = tx
+            .inputs
+            .iter()
+            .map(|i| lookup(&(i.prev_tx, i.output_index)).expect("verified above").clone())
+            .collect();
+
+        let parents = self.parents_of(&tx);
+        let ancestors = self.walk(parents.clone(), |id| self.parents_of(&self.entries[id].tx));
+        if ancestors.len() > ledger.config.max_mempool_ancestors {
+            return Err(Error::TooManyAncestors);
+        }
+
+        let conflicts: Vec<Hash> = tx
+            .inputs
+            .iter()
+            .filter_map(|i| self.spent_by.get(&(i.prev_tx, i.output_index)).copied())
+            .collect();
+        if !conflicts.is_empty() {
+            if !ledger.config.rbf_enabled {
+                return Err(Error::DoubleSpend);
+            }
+            let evicted = self.walk(conflicts, |id| self.children_of(id));
+            if ancestors.iter().any(|id| evicted.contains(id)) {
+                // Would leave `tx` spending outputs that no longer exist.
+                return Err(Error::DoubleSpend);
+            }
+            let replaced_fees: u64 = evicted.iter().map(|id| self.entries[id].fee).sum();
+            if fee <= replaced_fees {
+                return Err(Error::ReplacementFeeTooLow);
+            }
+            for id in &evicted {
+                self.remove(id);
+            }
+        }
+
+        let txid = tx.hash();
+        for inp in &tx.inputs {
+            self.spent_by.insert((inp.prev_tx, inp.output_index), txid);
+        }
+        self.entries.insert(txid, MempoolEntry { tx, fee, spent });
+        Ok(txid)
+    }
+
+    /// Removes and returns the transaction with id `txid`, e.g. once it has
+    /// been mined.
+    pub fn remove(&mut self, txid: &Hash) -> Option<MempoolEntry> {
+        let entry = self.entries.remove(txid)?;
+        for inp in &entry.tx.inputs {
+            self.spent_by.remove(&(inp.prev_tx, inp.output_index));
+        }
+        Some(entry)
+    }
+
+    /// Number of pooled transactions `txid` depends on, directly or
+    /// transitively.  `0` for unknown txids.
+    pub fn ancestor_count(&self, txid: &Hash) -> usize {
+        match self.entries.get(txid) {
+            Some(entry) => self.walk(self.parents_of(&entry.tx), |id| self.parents_of(&self.entries[id].tx)).len(),
+            None => 0,
+        }
+    }
+
+    /// Number of pooled transactions depending on `txid`, directly or
+    /// transitively.  `0` for unknown txids.
+    pub fn descendant_count(&self, txid: &Hash) -> usize {
+        if self.entries.contains_key(txid) {
+            self.walk(self.children_of(txid), |id| self.children_of(id)).len()
+        } else {
+            0
+        }
+    }
+
+    fn pooled_output(&self, key: &UtxoKey) -> Option<&TxOutput> {
+        self.entries.get(&key.0)?.tx.outputs.get(key.1 as usize)
+    }
+
+    /// Pooled transactions whose outputs `tx` spends.
+    fn parents_of(&self, tx: &Transaction) -> Vec<Hash> {
+        tx.inputs
+            .iter()
+            .map(|i| i.prev_tx)
+            .filter(|id| self.entries.contains_key(id))
+            .collect()
+    }
+
+    /// Pooled transactions spending outputs of `txid`.
+    fn children_of(&self, txid: &Hash) -> Vec<Hash> {
+        let outputs = self.entries.get(txid).map_or(0, |e| e.tx.outputs.len());
+        (0..outputs as u32)
+            .filter_map(|i| self.spent_by.get(&(*txid, i)).copied())
+            .collect()
+    }
+
+    /// Every txid reachable from `start` through `next`, `start` included.
+    fn walk(&self, start: Vec<Hash>, next: impl Fn(&Hash) -> Vec<Hash>) -> HashSet<Hash> {
+        let mut seen = HashSet::new();
+        let mut stack = start;
+        while let Some(id) = stack.pop() {
+            if seen.insert(id) {
+                stack.extend(next(&id));
+            }
+        }
+        seen
+    }
+
+    /// Returns `true` if the transaction with id `txid` is pooled.
+    pub fn contains(&self, txid: &Hash) -> bool {
+        self.entries.contains_key(txid)
+    }
+
+    /// Returns the pooled transaction with id `txid`, e.g. to answer a
+    /// peer's `getdata`.
+    pub fn get(&self, txid: &Hash) -> Option<&Transaction> {
+        self.entries.get(txid).map(|e| &e.tx)
+    }
+
+    /// Iterates the pooled transactions in unspecified order.
+    pub fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.entries.values().map(|e| &e.tx)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Counts pooled transactions per fee-rate bucket, highest rate first,
+    /// as `(bucket, count)` pairs for wallet fee estimation.
+    ///
+    /// Rates are fee per 1000 bytes of canonical encoding.  Buckets are
+    /// powers of two and hold rates from `bucket` up to `2 * bucket - 1`,
+    /// except bucket `0`, which holds zero-rate transactions.  Empty buckets
+    /// are omitted.  Summing counts from the front tells a wallet how many
+    /// transactions outbid a given rate.
+    pub fn fee_histogram(&self) -> Vec<(u64, usize)> {
+        let mut buckets: BTreeMap<u64, usize> = BTreeMap::new();
+        for entry in self.entries.values() {
+            let size = codec::encode_transaction(Endian::Big, &entry.tx).len() as u64;
+            let rate = entry.fee.saturating_mul(1000) / size;
+            let bucket = if rate == 0 { 0 } else { 1 << rate.ilog2() };
+            *buckets.entry(bucket).or_default() += 1;
+        }
+        buckets.into_iter().rev().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Total value of confirmed outputs owned by `pkh` that pooled
+    /// transactions spend.
+    pub fn pending_spends_for(&self, pkh: &[u8]) -> u64 {
+        self.entries
+            .values()
+            .flat_map(|e| e.tx.inputs.iter().zip(&e.spent))
+            .filter(|(i, o)| !self.entries.contains_key(&i.prev_tx) && o.pubkey_hash.as_slice() == pkh)
+            .map(|(_, o)| o.value)
+            .sum()
+    }
+
+    /// Value paid back to `pkh` by pooled transactions that spend its
+    /// outputs, i.e. pending change not itself spent in the pool.
+    fn pending_change_for(&self, pkh: &[u8]) -> u64 {
+        self.entries
+            .iter()
+            .filter(|(_, e)| e.spent.iter().any(|o| o.pubkey_hash.as_slice() == pkh))
+            .flat_map(|(id, e)| e.tx.outputs.iter().enumerate().map(move |(i, o)| ((*id, i as u32), o)))
+            .filter(|(key, o)| !self.spent_by.contains_key(key) && o.pubkey_hash.as_slice() == pkh)
+            .map(|(_, o)| o.value)
+            .sum()
+    }
+}
+
+/// Balance `pkh` can spend once pending transactions are taken into account:
+/// the confirmed balance minus outputs being spent in the mempool, plus the
+/// change those spends return to `pkh`.
+///
+/// Incoming payments from other owners are not counted until confirmed.
+pub fn available_balance(ledger: &Ledger, mempool: &Mempool, pkh: &[u8]) -> u64 {
+    ledger
+        .balance_for_pubkey_hash(pkh)
+        .saturating_sub(mempool.pending_spends_for(pkh))
+        + mempool.pending_change_for(pkh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::ConfigBuilder, Block, TxInput, TEST_PUBKEY};
+
+    fn unsigned_ledger(genesis: &Block) -> Ledger {
+        Ledger::with_config(genesis, ConfigBuilder::new().allow_unsigned(true).finish()).unwrap()
+    }
+
+    fn funded_ledger() -> (Ledger, Hash) {
+        let coinbase = Transaction::coinbase(1, vec![TxOutput { value: 50, pubkey_hash: vec![1] }], b"");
+        let genesis = Block::new(1, [0u8; 32], vec![coinbase.clone()], 0);
+        (unsigned_ledger(&genesis), coinbase.hash())
+    }
+
+    fn chain(ledger: &Ledger, pool: &mut Mempool, mut prev: Hash, len: u64) -> Vec<Hash> {
+        (0..len)
+            .map(|i| {
+                prev = pool.add(spend(prev, vec![TxOutput { value: 49 - i, pubkey_hash: vec![1] }]), ledger).unwrap();
+                prev
+            })
+            .collect()
+    }
+
+    fn spend(prev_tx: Hash, outputs: Vec<TxOutput>) -> Transaction {
+        let input = TxInput { prev_tx, output_index: 0, pubkey: TEST_PUBKEY.to_vec(), signature: vec![], scheme: 0 };
+        Transaction { inputs: vec![input], outputs, metadata: None }
+    }
+
+    #[test]
+    fn pending_spend_reduces_available_balance() {
+        let (ledger, prev) = funded_ledger();
+        let mut pool = Mempool::new();
+        let tx = spend(
+            prev,
+            vec![
+                TxOutput { value: 20, pubkey_hash: vec![2] },
+                TxOutput { value: 25, pubkey_hash: vec![1] },
+            ],
+        );
+        pool.add(tx, &ledger).unwrap();
+
+        assert_eq!(ledger.balance_for_pubkey_hash(&[1]), 50);
+        assert_eq!(pool.pending_spends_for(&[1]), 50);
+        assert_eq!(available_balance(&ledger, &pool, &[1]), 25, "change counts, fee does not");
+        assert_eq!(available_balance(&ledger, &pool, &[2]), 0, "unconfirmed receipts do not count");
+    }
+
+    #[test]
+    fn conflicting_spend_rejected_without_rbf() {
+        let (mut ledger, prev) = funded_ledger();
+        ledger.config.rbf_enabled = false;
+        let mut pool = Mempool::new();
+        let txid = pool.add(spend(prev, vec![TxOutput { value: 50, pubkey_hash: vec![2] }]), &ledger).unwrap();
+        let conflict = spend(prev, vec![TxOutput { value: 49, pubkey_hash: vec![3] }]);
+        assert_eq!(pool.add(conflict.clone(), &ledger), Err(Error::DoubleSpend));
+
+        pool.remove(&txid).unwrap();
+        assert!(pool.add(conflict, &ledger).is_ok());
+    }
+
+    #[test]
+    fn higher_fee_replacement_evicts_original() {
+        let (ledger, prev) = funded_ledger();
+        let mut pool = Mempool::new();
+        let original = pool.add(spend(prev, vec![TxOutput { value: 48, pubkey_hash: vec![2] }]), &ledger).unwrap();
+        let bump = pool.add(spend(prev, vec![TxOutput { value: 45, pubkey_hash: vec![2] }]), &ledger).unwrap();
+
+        assert_eq!(pool.len(), 1);
+        assert!(pool.remove(&original).is_none());
+        assert_eq!(pool.remove(&bump).unwrap().fee, 5);
+    }
+
+    #[test]
+    fn equal_or_lower_fee_replacement_rejected() {
+        let (ledger, prev) = funded_ledger();
+        let mut pool = Mempool::new();
+        let original = pool.add(spend(prev, vec![TxOutput { value: 45, pubkey_hash: vec![2] }]), &ledger).unwrap();
+        let equal = spend(prev, vec![TxOutput { value: 45, pubkey_hash: vec![3] }]);
+        let lower = spend(prev, vec![TxOutput { value: 46, pubkey_hash: vec![2] }]);
+        assert_eq!(pool.add(equal, &ledger), Err(Error::ReplacementFeeTooLow));
+        assert_eq!(pool.add(lower, &ledger), Err(Error::ReplacementFeeTooLow));
+        assert_eq!(pool.remove(&original).unwrap().fee, 5);
+    }
+
+    #[test]
+    fn ancestor_limit_caps_unconfirmed_chains() {
+        let (mut ledger, prev) = funded_ledger();
+        ledger.config.max_mempool_ancestors = 3;
+        let mut pool = Mempool::new();
+        let ids = chain(&ledger, &mut pool, prev, 4);
+
+        assert_eq!(pool.ancestor_count(&ids[0]), 0);
+        assert_eq!(pool.ancestor_count(&ids[3]), 3);
+        assert_eq!(pool.descendant_count(&ids[0]), 3);
+        assert_eq!(pool.descendant_count(&ids[3]), 0);
+        assert_eq!(available_balance(&ledger, &pool, &[1]), 46);
+
+        let fifth = spend(ids[3], vec![TxOutput { value: 40, pubkey_hash: vec![1] }]);
+        assert_eq!(pool.add(fifth.clone(), &ledger), Err(Error::TooManyAncestors));
+        pool.remove(&ids[0]).unwrap();
+        assert_eq!(pool.ancestor_count(&ids[3]), 2);
+        assert!(pool.add(fifth, &ledger).is_ok());
+    }
+
+    #[test]
+    fn replacement_evicts_descendants() {
+        let (ledger, prev) = funded_ledger();
+        let mut pool = Mempool::new();
+        chain(&ledger, &mut pool, prev, 3);
+
+        // The chain pays 1 + 1 + 1; matching that is not enough.
+        let low = spend(prev, vec![TxOutput { value: 47, pubkey_hash: vec![2] }]);
+        assert_eq!(pool.add(low, &ledger), Err(Error::ReplacementFeeTooLow));
+        let bump = pool.add(spend(prev, vec![TxOutput { value: 46, pubkey_hash: vec![2] }]), &ledger).unwrap();
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.remove(&bump).unwrap().fee, 4);
+    }
+
+    #[test]
+    fn fee_histogram_buckets_by_rate() {
+        let outputs = vec![TxOutput { value: 50, pubkey_hash: vec![1] }; 5];
+        let coinbase = Transaction::coinbase(1, outputs, b"");
+        let ledger = unsigned_ledger(&Block::new(1, [0u8; 32], vec![coinbase.clone()], 0));
+        let mut pool = Mempool::new();
+        assert!(pool.fee_histogram().is_empty());
+
+        // Every spend encodes to 118 bytes, so a fee of 1 is a rate of 8.
+        for (index, fee) in [1, 2, 3, 4, 10].into_iter().enumerate() {
+            let input = TxInput { prev_tx: coinbase.hash(), output_index: index as u32, pubkey: TEST_PUBKEY.to_vec(), signature: vec![], scheme: 0 };
+            let output = TxOutput { value: 50 - fee, pubkey_hash: vec![2] };
+            pool.add(Transaction { inputs: vec![input], outputs: vec![output], metadata: None }, &ledger).unwrap();
+        }
+        assert_eq!(pool.fee_histogram(), vec![(64, 1), (32, 1), (16, 2), (8, 1)]);
+    }
+
+    #[test]
+    fn lookup_by_txid() {
+        let (ledger, prev) = funded_ledger();
+        let mut pool = Mempool::new();
+        let tx = spend(prev, vec![TxOutput { value: 45, pubkey_hash: vec![2] }]);
+        assert!(!pool.contains(&tx.hash()));
+        let txid = pool.add(tx.clone(), &ledger).unwrap();
+        assert!(pool.contains(&txid));
+        assert_eq!(pool.get(&txid), Some(&tx));
+        assert_eq!(pool.get(&prev), None);
+    }
+}
@@ -0,0 +1,151 @@
+//! Binary Merkle trees over transaction hashes.
+//!
+//! Each level pairs adjacent nodes and hashes `hash256(left || right)`; a
+//! level with an odd number of nodes pairs its last node with itself
+//! (Bitcoin-style).  A single leaf is its own root.
+//!
+//! Inclusion proofs list the sibling at every level from the leaf upwards as
+//! `(sibling, sibling_is_right)`, which is all an SPV client needs to
+//! recompute the root from a transaction hash.
+
+use crate::{hash256, Hash};
+
+fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+    let mut buf = [0u8; 64];
+    buf[..32].copy_from_slice(left);
+    buf[32..].copy_from_slice(right);
+    hash256(&buf)
+}
+
+fn next_level(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+        .collect()
+}
+
+/// Computes the Merkle root of `leaves`.  The root of no leaves is all
+/// zeros.
+pub fn root(leaves: &[Hash]) -> Hash {
+    if leaves.is_empty() {
+        return [0u8; 32];
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level[0]
+}
+
+/// Same as [`root`] but consumes `leaves` one at a time, keeping only one
+/// pending node per tree level (`O(log n)` memory) instead of the whole
+/// leaf list.
+pub fn root_from<I: IntoIterator<Item = Hash>>(leaves: I) -> Hash {
+    // `pending[l]` holds the left node at level `l` awaiting its sibling;
+    // it is occupied exactly when bit `l` of `count` is set.
+    let mut pending: Vec<Hash> = Vec::new();
+    let mut count = 0u64;
+    for leaf in leaves {
+        let mut node = leaf;
+        let mut level = 0;
+        while count & (1 << level) != 0 {
+            node = hash_pair(&pending[level], &node);
+            level += 1;
+        }
+        if level == pending.len() {
+            pending.push(node);
+        } else {
+            pending[level] = node;
+        }
+        count += 1;
+    }
+    if count == 0 {
+        return [0u8; 32];
+    }
+    // Sweep up the rightmost branch, pairing odd nodes with themselves.
+    let mut level = count.trailing_zeros() as usize;
+    let mut node = pending[level];
+    while count != 1 << level {
+        node = hash_pair(&node, &node);
+        count += 1 << level;
+        level += 1;
+        while count & (1 << level) == 0 {
+            node = hash_pair(&pending[level], &node);
+            level += 1;
+        }
+    }
+    node
+}
+
+/// Builds the inclusion proof for `leaves[index]`, or `None` if the index is
+/// out of range.
+pub fn proof(leaves: &[Hash], index: usize) -> Option<Vec<(Hash, bool)>> {
+    if index >= leaves.len() {
+        return None;
+    }
+    let mut path = Vec::new();
+    let mut level = leaves.to_vec();
+    let mut idx = index;
+    while level.len() > 1 {
+        let sibling_is_right = idx.is_multiple_of(2);
+        let sibling = if sibling_is_right {
+            *level.get(idx + 1).unwrap_or(&level[idx])
+        } else {
+            level[idx - 1]
+        };
+        path.push((sibling, sibling_is_right));
+        level = next_level(&level);
+        idx /= 2;
+    }
+    Some(path)
+}
+
+/// Returns `true` if `proof` links `leaf` to `root`.
+pub fn verify_proof(leaf: &Hash, proof: &[(Hash, bool)], root: &Hash) -> bool {
+    let computed = proof.iter().fold(*leaf, |node, (sibling, sibling_is_right)| {
+        if *sibling_is_right {
+            hash_pair(&node, sibling)
+        } else {
+            hash_pair(sibling, &node)
+        }
+    });
+    computed == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(n: u8) -> Vec<Hash> {
+        (0..n).map(|i| [i; 32]).collect()
+    }
+
+    #[test]
+    fn single_leaf_is_root() {
+        assert_eq!(root(&[]), [0u8; 32]);
+        assert_eq!(root(&[[7u8; 32]]), [7u8; 32]);
+        assert_eq!(proof(&[[7u8; 32]], 0), Some(vec![]));
+    }
+
+    #[test]
+    fn streaming_root_matches_root() {
+        for n in 0..=33 {
+            let leaves = leaves(n);
+            assert_eq!(root_from(leaves.iter().copied()), root(&leaves), "n={n}");
+        }
+    }
+
+    #[test]
+    fn proofs_verify_for_every_leaf() {
+        for n in 1..=9 {
+            let leaves = leaves(n);
+            let root = root(&leaves);
+            for (i, leaf) in leaves.iter().enumerate() {
+                let path = proof(&leaves, i).unwrap();
+                assert!(verify_proof(leaf, &path, &root), "n={n} i={i}");
+                assert!(!verify_proof(&[0xFF; 32], &path, &root));
+            }
+            assert!(proof(&leaves, n as usize).is_none());
+        }
+    }
+}
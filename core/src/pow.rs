@@ -1,14 +1,90 @@
 //! Proof-of-Work helpers.
 //!
-//! Currently the consensus algorithm is a simplified *leading-zero* target: a
-//! hash is valid if it begins with `difficulty` zero bits.  In production we
-//! will switch to a proper *target value* representation compatible with
-//! Bitcoin so difficulty can be adjusted by changing the target, not the bit
-//! count.
+//! By default the consensus algorithm is a simplified *leading-zero* target:
+//! a hash is valid if it begins with `difficulty` zero bits.  Networks
+//! configured with [`PowMode::Target`] instead read `difficulty` as a
+//! Bitcoin-style compact target, so difficulty can be adjusted by changing
+//! the target, not the bit count; the header field keeps its `u32` width
+//! either way.  [`retarget`] performs the adjustment on 256-bit targets;
+//! [`target_from_difficulty`], [`difficulty_of_hash`],
+//! [`target_from_compact`] and [`compact_from_target`] convert between the
+//! representations.
 //!
 //! All functions are pure and stateless so they can be used from any thread.
+//! Everything except the header-level helpers ([`header_meets_difficulty`],
+//! [`verify_header_chain`], [`mine_range`], [`mine_parallel`]) is available
+//! in `no_std` builds.  [`MiningStats`] likewise needs `std`.
 
-use crate::Hash;
+use crate::{hash256, Hash};
+#[cfg(feature = "std")]
+use crate::{BlockHeader, Error, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Hash function whose output must meet the difficulty target.
+///
+/// Changing it changes which nonces are valid, so it is fixed per network at
+/// genesis via `Config::pow_algo`.  Block ids (`BlockHeader::hash`) are
+/// always the single Blake2b hash; only the proof-of-work hash varies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PowAlgo {
+    /// `hash256(header)`, identical to the block id.
+    #[default]
+    Blake2b,
+    /// `hash256(hash256(header))`.
+    Blake2bDouble,
+}
+
+impl PowAlgo {
+    /// Hashes `data` with this algorithm.
+    pub fn hash(self, data: &[u8]) -> Hash {
+        self.finish(hash256(data))
+    }
+
+    /// Applies the remaining rounds to an already computed `hash256`.
+    pub(crate) fn finish(self, first: Hash) -> Hash {
+        match self {
+            PowAlgo::Blake2b => first,
+            PowAlgo::Blake2bDouble => hash256(&first),
+        }
+    }
+}
+
+/// How a header's `difficulty` field is interpreted.
+///
+/// Fixed per network at genesis via `Config::pow_mode`, so existing
+/// networks keep the bit-count rule while new ones use targets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PowMode {
+    /// `difficulty` counts required leading zero bits.
+    #[default]
+    BitCount,
+    /// `difficulty` is a compact target (Bitcoin's `nBits`); the hash, read
+    /// as a big-endian number, must not exceed it.
+    Target,
+}
+
+impl PowMode {
+    /// Largest hash meeting `difficulty` under this mode; all zeros for a
+    /// malformed compact target.
+    pub fn target(self, difficulty: u32) -> Hash {
+        match self {
+            PowMode::BitCount => target_from_difficulty(difficulty),
+            PowMode::Target => target_from_compact(difficulty).unwrap_or([0; 32]),
+        }
+    }
+
+    /// Leading-zero-bit equivalent of `difficulty`, so floors such as
+    /// `Config::min_difficulty` stay in bits under either mode.
+    pub fn bit_difficulty(self, difficulty: u32) -> u32 {
+        match self {
+            PowMode::BitCount => difficulty,
+            PowMode::Target => target_from_compact(difficulty).map_or(0, |t| difficulty_of_hash(&t)),
+        }
+    }
+}
 
 /// Returns `true` if `hash` meets the difficulty target.
 ///
@@ -36,3 +112,328 @@ pub fn hash_meets_difficulty(hash: &Hash, difficulty: u32) -> bool {
     let next_byte = hash[zero_bytes];
     next_byte.leading_zeros() as u8 >= zero_bits
 }
+
+/// Same as [`hash_meets_difficulty`] but interprets `difficulty` under
+/// `mode`.
+pub fn hash_meets_difficulty_in(hash: &Hash, difficulty: u32, mode: PowMode) -> bool {
+    match mode {
+        PowMode::BitCount => hash_meets_difficulty(hash, difficulty),
+        PowMode::Target => target_from_compact(difficulty).is_some_and(|target| *hash <= target),
+    }
+}
+
+/// [`hash_meets_difficulty`] with the per-difficulty arithmetic done once,
+/// for mining loops that test many hashes against the same difficulty.
+///
+/// Bit-count difficulties above 256 and malformed compact targets can never
+/// be met.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DifficultyCheck(Check);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Check {
+    Bits {
+        /// Leading bytes that must be zero.
+        zero_bytes: usize,
+        /// Largest value allowed for the byte after them.
+        max_next: u8,
+    },
+    Target(Option<Hash>),
+}
+
+impl DifficultyCheck {
+    pub fn new(difficulty: u32) -> Self {
+        Self::with_mode(difficulty, PowMode::BitCount)
+    }
+
+    pub fn with_mode(difficulty: u32, mode: PowMode) -> Self {
+        Self(match mode {
+            PowMode::BitCount if difficulty > 256 => Check::Bits { zero_bytes: usize::MAX, max_next: 0 },
+            PowMode::BitCount => {
+                Check::Bits { zero_bytes: (difficulty / 8) as usize, max_next: 0xFF >> (difficulty % 8) }
+            }
+            PowMode::Target => Check::Target(target_from_compact(difficulty)),
+        })
+    }
+
+    /// Returns `true` if `hash` meets the difficulty.
+    pub fn check(&self, hash: &Hash) -> bool {
+        match self.0 {
+            Check::Bits { zero_bytes, max_next } => match hash.get(..zero_bytes) {
+                Some(prefix) if prefix.iter().all(|&b| b == 0) => {
+                    hash.get(zero_bytes).is_none_or(|&b| b <= max_next)
+                }
+                _ => false,
+            },
+            Check::Target(target) => target.is_some_and(|target| *hash <= target),
+        }
+    }
+}
+
+/// How a pool should account for a hash a worker submitted, see
+/// [`is_valid_share`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShareResult {
+    /// Meets the block difficulty: the block can be published.
+    Block,
+    /// Meets only the share difficulty: credit the worker.
+    Share,
+    /// Meets neither.
+    Reject,
+}
+
+/// Classifies a worker's `hash` against the pool's `share_difficulty` and
+/// the network's `block_difficulty`, both leading-zero bit counts.
+///
+/// A block solution always counts, even if the pool set its share difficulty
+/// above the block difficulty.
+pub fn is_valid_share(hash: &Hash, share_difficulty: u32, block_difficulty: u32) -> ShareResult {
+    if hash_meets_difficulty(hash, block_difficulty) {
+        ShareResult::Block
+    } else if hash_meets_difficulty(hash, share_difficulty) {
+        ShareResult::Share
+    } else {
+        ShareResult::Reject
+    }
+}
+
+/// What a finished mining run did and found, as returned by
+/// [`Block::mine_with_stats`](crate::Block::mine_with_stats).
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MiningStats {
+    /// Hashes computed, the winning one included.
+    pub hashes: u64,
+    /// Wall-clock time the search took.
+    pub elapsed: std::time::Duration,
+    /// Nonce of the solution.
+    pub nonce: u64,
+    /// Proof-of-work hash of the solution.
+    pub hash: Hash,
+    /// Leading zero bits of `hash` ([`difficulty_of_hash`]); at least the
+    /// difficulty required, more when the miner got lucky.
+    pub achieved_difficulty: u32,
+}
+
+#[cfg(feature = "std")]
+impl MiningStats {
+    /// Hashes per second; `0.0` if no measurable time passed.
+    pub fn hash_rate(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.hashes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Returns `true` if `header`'s proof-of-work hash under `algo` meets the
+/// difficulty it declares.
+#[cfg(feature = "std")]
+pub fn header_meets_difficulty(header: &BlockHeader, algo: PowAlgo) -> bool {
+    hash_meets_difficulty(&header.pow_hash(algo), header.difficulty)
+}
+
+/// Same as [`header_meets_difficulty`] but interprets the declared
+/// difficulty under `mode`.
+#[cfg(feature = "std")]
+pub fn header_meets_difficulty_in(header: &BlockHeader, algo: PowAlgo, mode: PowMode) -> bool {
+    hash_meets_difficulty_in(&header.pow_hash(algo), header.difficulty, mode)
+}
+
+/// Searches nonces in `[start, end)` for one making `header` meet its
+/// difficulty under the default [`PowAlgo`], returning the first found.
+///
+/// Ranges are independent, so a coordinator can hand disjoint ranges to
+/// separate workers or machines.
+#[cfg(feature = "std")]
+pub fn mine_range(header: &BlockHeader, start: u64, end: u64) -> Option<u64> {
+    mine_range_with(header, PowAlgo::default(), PowMode::default(), start, end)
+}
+
+/// Same as [`mine_range`] but for proof-of-work under `algo`, with the
+/// declared difficulty read under `mode`.
+#[cfg(feature = "std")]
+pub fn mine_range_with(header: &BlockHeader, algo: PowAlgo, mode: PowMode, start: u64, end: u64) -> Option<u64> {
+    let check = DifficultyCheck::with_mode(header.difficulty, mode);
+    let mut candidate = header.clone();
+    (start..end).find(|&nonce| {
+        candidate.nonce = nonce;
+        check.check(&candidate.pow_hash(algo))
+    })
+}
+
+/// Searches for a nonce making `header` meet its difficulty under `algo`
+/// and `mode` using `workers` threads, returning the first one found (not
+/// necessarily the lowest).
+///
+/// Instead of splitting the nonce space up front, each worker claims the
+/// next unsearched range from a shared counter whenever it finishes one, so
+/// fast and slow workers stay busy until a solution turns up.  Ranges hold
+/// about `2^difficulty` nonces (difficulty in bits, see
+/// [`PowMode::bit_difficulty`]), one expected solution, bounded to
+/// `[2^8, 2^20]` so workers notice another's success quickly.
+#[cfg(feature = "std")]
+pub fn mine_parallel(header: &BlockHeader, algo: PowAlgo, mode: PowMode, workers: usize) -> Option<u64> {
+    use std::sync::{atomic::{AtomicU64, Ordering}, OnceLock};
+
+    let stride = 1u64 << mode.bit_difficulty(header.difficulty).clamp(8, 20);
+    let next = AtomicU64::new(0);
+    let found = OnceLock::new();
+    std::thread::scope(|s| {
+        for _ in 0..workers.max(1) {
+            s.spawn(|| {
+                while found.get().is_none() {
+                    let Ok(start) = next.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_add(stride))
+                    else {
+                        break;
+                    };
+                    if let Some(nonce) = mine_range_with(header, algo, mode, start, start.saturating_add(stride)) {
+                        let _ = found.set(nonce);
+                    }
+                }
+            });
+        }
+    });
+    found.into_inner()
+}
+
+/// Validates a header-only chain as downloaded by an SPV client.
+///
+/// Each header must meet its declared difficulty under `algo` and `mode`
+/// (see [`header_meets_difficulty_in`]) and every header after the
+/// first must reference its predecessor via `prev_hash` and carry the next
+/// sequential index.  Transactions are not inspected, so this is far cheaper
+/// than full block validation.
+#[cfg(feature = "std")]
+pub fn verify_header_chain(headers: &[BlockHeader], algo: PowAlgo, mode: PowMode) -> Result<()> {
+    let mut prev: Option<&BlockHeader> = None;
+    for header in headers {
+        if let Some(parent) = prev {
+            if header.prev_hash != parent.hash() {
+                return Err(Error::PrevHashMismatch);
+            }
+            if header.index != parent.index + 1 {
+                return Err(Error::NonSequentialHeight);
+            }
+        }
+        if !header_meets_difficulty_in(header, algo, mode) {
+            return Err(Error::DifficultyFail);
+        }
+        prev = Some(header);
+    }
+    Ok(())
+}
+
+/// Number of leading zero bits in `hash`, i.e. the highest difficulty it
+/// satisfies.  Also converts a target back to a difficulty.
+pub fn difficulty_of_hash(hash: &Hash) -> u32 {
+    let mut bits = 0;
+    for &byte in hash {
+        bits += byte.leading_zeros();
+        if byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// The largest hash meeting `difficulty`: `difficulty` zero bits followed by
+/// ones, as a big-endian 256-bit number.
+pub fn target_from_difficulty(difficulty: u32) -> Hash {
+    let mut target = [0xFFu8; 32];
+    let difficulty = difficulty.min(256) as usize;
+    for byte in target.iter_mut().take(difficulty / 8) {
+        *byte = 0;
+    }
+    if difficulty < 256 {
+        target[difficulty / 8] >>= difficulty % 8;
+    }
+    target
+}
+
+/// Expands a compact target: the high byte is a length `e` in bytes and the
+/// low 23 bits a mantissa `m`, giving `m * 256^(e - 3)`.
+///
+/// `None` if the sign bit (`0x0080_0000`) is set with a non-zero mantissa
+/// or the target does not fit in 256 bits.
+pub fn target_from_compact(bits: u32) -> Option<Hash> {
+    let size = (bits >> 24) as usize;
+    let mantissa = bits & 0x007F_FFFF;
+    if bits & 0x0080_0000 != 0 && mantissa != 0 {
+        return None;
+    }
+    let mut target = [0u8; 32];
+    let bytes = &mantissa.to_be_bytes()[1..];
+    if size <= 3 {
+        let value = mantissa >> (8 * (3 - size));
+        target[28..].copy_from_slice(&value.to_be_bytes());
+        return Some(target);
+    }
+    // The mantissa's leading zero bytes may be pushed past the top.
+    let skip = bytes.iter().take_while(|&&b| b == 0).count();
+    if size - skip > 32 {
+        return None;
+    }
+    for (i, &b) in bytes.iter().enumerate().skip(skip) {
+        target[i + 32 - size] = b;
+    }
+    Some(target)
+}
+
+/// Smallest-exponent compact encoding of `target`, rounding down to the
+/// three most significant bytes.  Inverse of [`target_from_compact`] for
+/// targets it can represent exactly.
+pub fn compact_from_target(target: &Hash) -> u32 {
+    let Some(first) = target.iter().position(|&b| b != 0) else {
+        return 0;
+    };
+    let mut size = 32 - first;
+    let mut mantissa = target[first..].iter().take(3).fold(0u32, |acc, &b| (acc << 8) | b as u32);
+    if size < 3 {
+        mantissa <<= 8 * (3 - size);
+    }
+    // Keep the sign bit clear.
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    ((size as u32) << 24) | mantissa
+}
+
+/// Scales `old_target` by `actual_timespan / target_timespan`.
+///
+/// `actual_timespan` is clamped to `[target_timespan / 4, target_timespan *
+/// 4]` first, so a single adjustment moves the target by at most a factor of
+/// four either way.  The result saturates at the all-ones target.
+pub fn retarget(old_target: &Hash, actual_timespan: u64, target_timespan: u64) -> Hash {
+    let target_timespan = target_timespan.max(1);
+    let actual = actual_timespan.clamp((target_timespan / 4).max(1), target_timespan.saturating_mul(4));
+
+    // Big-endian 64-bit limbs with one extra limb of headroom for the product.
+    let mut limbs = [0u64; 5];
+    for (i, chunk) in old_target.chunks(8).enumerate() {
+        limbs[i + 1] = u64::from_be_bytes(chunk.try_into().expect("8-byte chunk"));
+    }
+    let mut carry = 0u128;
+    for limb in limbs.iter_mut().rev() {
+        let product = (*limb as u128) * (actual as u128) + carry;
+        *limb = product as u64;
+        carry = product >> 64;
+    }
+    let mut rem = 0u128;
+    for limb in limbs.iter_mut() {
+        let cur = (rem << 64) | *limb as u128;
+        *limb = (cur / target_timespan as u128) as u64;
+        rem = cur % target_timespan as u128;
+    }
+    if limbs[0] != 0 {
+        return [0xFF; 32];
+    }
+    let mut out = [0u8; 32];
+    for (i, limb) in limbs[1..].iter().enumerate() {
+        out[i * 8..(i + 1) * 8].copy_from_slice(&limb.to_be_bytes());
+    }
+    out
+}
@@ -0,0 +1,133 @@
+//! Cache of transactions whose signatures already verified.
+//!
+//! A transaction is usually verified when it enters the mempool and again
+//! when a block including it is applied.  [`SigCache`] remembers the
+//! `(txid, signing_message)` of every transaction that passed, so the second
+//! check is a lookup.  The txid covers the signatures themselves, so a
+//! transaction with any signature changed is a cache miss.
+//!
+//! The cache holds at most `capacity` entries and evicts the least recently
+//! used one.  It is internally synchronised so the parallel signature phase
+//! of block validation can share it.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use crate::{Hash, Result, Transaction};
+
+/// Default [`SigCache`] capacity of a new [`crate::ledger::Ledger`].
+pub const DEFAULT_SIG_CACHE_SIZE: usize = 50_000;
+
+type Key = (Hash, Hash);
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// Entry -> tick of its last use.
+    entries: HashMap<Key, u64>,
+    /// Tick -> entry, oldest first.
+    by_use: BTreeMap<u64, Key>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl Inner {
+    fn touch(&mut self, key: Key) {
+        self.tick += 1;
+        if let Some(old) = self.entries.insert(key, self.tick) {
+            self.by_use.remove(&old);
+        }
+        self.by_use.insert(self.tick, key);
+    }
+}
+
+/// LRU set of transactions with verified signatures.
+#[derive(Debug)]
+pub struct SigCache {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+impl Default for SigCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIG_CACHE_SIZE)
+    }
+}
+
+impl SigCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, inner: Mutex::default() }
+    }
+
+    /// Runs `verify` unless `tx` is cached, caching `tx` if it passes.
+    pub fn verify_with(&self, tx: &Transaction, verify: impl FnOnce() -> Result<()>) -> Result<()> {
+        let key = (tx.hash(), tx.signing_message());
+        {
+            let mut inner = self.lock();
+            if inner.entries.contains_key(&key) {
+                inner.hits += 1;
+                inner.touch(key);
+                return Ok(());
+            }
+            inner.misses += 1;
+        }
+        // Verify without holding the lock so parallel callers do not queue.
+        verify()?;
+        let mut inner = self.lock();
+        inner.touch(key);
+        while inner.entries.len() > self.capacity {
+            let (_, oldest) = inner.by_use.pop_first().expect("entries and by_use agree");
+            inner.entries.remove(&oldest);
+        }
+        Ok(())
+    }
+
+    /// Lookups answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.lock().hits
+    }
+
+    /// Lookups that had to verify.
+    pub fn misses(&self) -> u64 {
+        self.lock().misses
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, TxOutput};
+
+    fn tx(n: u8) -> Transaction {
+        Transaction::coinbase(n as u64, vec![TxOutput { value: 1, pubkey_hash: vec![n] }], b"")
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = SigCache::new(2);
+        let ok = || Ok(());
+        cache.verify_with(&tx(1), ok).unwrap();
+        cache.verify_with(&tx(2), ok).unwrap();
+        cache.verify_with(&tx(1), || panic!("cached")).unwrap();
+        cache.verify_with(&tx(3), ok).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert_eq!((cache.hits(), cache.misses()), (1, 3));
+
+        // tx 2 was the least recently used and got evicted.
+        assert_eq!(cache.verify_with(&tx(2), || Err(Error::BadSignature)), Err(Error::BadSignature));
+        cache.verify_with(&tx(1), || panic!("cached")).unwrap();
+        assert_eq!(cache.len(), 2, "failures are not cached");
+    }
+}
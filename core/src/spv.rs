@@ -0,0 +1,123 @@
+//! Simplified payment verification.
+//!
+//! A [`LightClient`] keeps only block headers.  Each new header must link to
+//! the current tip and satisfy its proof-of-work, and transactions are
+//! checked against a stored header's Merkle root using an inclusion proof
+//! obtained from a full node (see [`crate::Block::merkle_proof`]).
+
+use crate::{
+    merkle,
+    pow::{self, PowAlgo, PowMode},
+    BlockHeader, Error, Hash, Result,
+};
+
+/// Validated header chain starting at genesis.
+#[derive(Debug, Clone)]
+pub struct LightClient {
+    /// `headers[h - 1]` is the header at height `h`.
+    headers: Vec<BlockHeader>,
+    /// Proof-of-work rules of the network, as in `Config::pow_algo` and
+    /// `Config::pow_mode`.
+    algo: PowAlgo,
+    mode: PowMode,
+}
+
+impl LightClient {
+    /// Starts a client from the genesis header, which must meet its own
+    /// difficulty under `algo` and `mode`.
+    pub fn new(genesis: BlockHeader, algo: PowAlgo, mode: PowMode) -> Result<Self> {
+        if genesis.index != 1 || genesis.prev_hash != [0u8; 32] {
+            return Err(Error::InvalidGenesis);
+        }
+        if !pow::header_meets_difficulty_in(&genesis, algo, mode) {
+            return Err(Error::DifficultyFail);
+        }
+        Ok(Self { headers: vec![genesis], algo, mode })
+    }
+
+    /// Appends `header` if it extends the tip and meets its difficulty.
+    pub fn add_header(&mut self, header: BlockHeader) -> Result<()> {
+        pow::verify_header_chain(&[self.tip().clone(), header.clone()], self.algo, self.mode)?;
+        self.headers.push(header);
+        Ok(())
+    }
+
+    /// Height of the best header (genesis == 1).
+    pub fn height(&self) -> u64 {
+        self.headers.len() as u64
+    }
+
+    pub fn tip(&self) -> &BlockHeader {
+        self.headers.last().expect("light client always holds genesis")
+    }
+
+    /// Returns the header at `height`, if known.
+    pub fn header_at(&self, height: u64) -> Option<&BlockHeader> {
+        let idx = usize::try_from(height.checked_sub(1)?).ok()?;
+        self.headers.get(idx)
+    }
+
+    /// Returns `true` if `proof` shows `tx_hash` is included in the block at
+    /// `block_height`.  Unknown heights never verify.
+    pub fn verify_transaction(&self, tx_hash: &Hash, proof: &[(Hash, bool)], block_height: u64) -> bool {
+        self.header_at(block_height)
+            .is_some_and(|h| merkle::verify_proof(tx_hash, proof, &h.merkle_root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Block, Transaction, TxOutput};
+
+    fn coinbase(height: u64) -> Transaction {
+        Transaction::coinbase(height, vec![TxOutput { value: 50, pubkey_hash: vec![1] }], b"")
+    }
+
+    fn payment(value: u64) -> Transaction {
+        Transaction { inputs: vec![], outputs: vec![TxOutput { value, pubkey_hash: vec![2] }], metadata: None }
+    }
+
+    #[test]
+    fn headers_link_and_transactions_verify() {
+        let genesis = Block::new(1, [0u8; 32], vec![coinbase(1)], 4).mine();
+        let mut client = LightClient::new(genesis.header.clone(), PowAlgo::default(), PowMode::default()).unwrap();
+
+        let txs = vec![coinbase(2), payment(1), payment(2)];
+        let block = Block::new(2, genesis.hash(), txs, 4).mine();
+        client.add_header(block.header.clone()).unwrap();
+        assert_eq!(client.height(), 2);
+
+        let tx_hash = block.transactions[2].hash();
+        let proof = block.merkle_proof(2).unwrap();
+        assert!(client.verify_transaction(&tx_hash, &proof, 2));
+        assert!(!client.verify_transaction(&tx_hash, &proof, 1));
+        assert!(!client.verify_transaction(&tx_hash, &proof, 3));
+        assert!(!client.verify_transaction(&payment(3).hash(), &proof, 2));
+    }
+
+    #[test]
+    fn unlinked_header_rejected() {
+        let genesis = Block::new(1, [0u8; 32], vec![coinbase(1)], 0);
+        let mut client = LightClient::new(genesis.header, PowAlgo::default(), PowMode::default()).unwrap();
+        let orphan = Block::new(2, [9u8; 32], vec![coinbase(2)], 0);
+        assert_eq!(client.add_header(orphan.header), Err(Error::PrevHashMismatch));
+        let unmined = Block::new(2, client.tip().hash(), vec![coinbase(2)], 64);
+        assert_eq!(client.add_header(unmined.header), Err(Error::DifficultyFail));
+        assert_eq!(client.height(), 1);
+    }
+
+    #[test]
+    fn headers_checked_under_network_pow_rules() {
+        let (algo, mode) = (PowAlgo::Blake2bDouble, PowMode::Target);
+        let compact = pow::compact_from_target(&pow::target_from_difficulty(8));
+        let genesis = Block::new(1, [0u8; 32], vec![coinbase(1)], compact).mine_in(algo, mode);
+        let mut client = LightClient::new(genesis.header.clone(), algo, mode).unwrap();
+        let next = Block::new(2, genesis.hash(), vec![coinbase(2)], compact).mine_in(algo, mode);
+        client.add_header(next.header).unwrap();
+        assert_eq!(client.height(), 2);
+
+        let bit_count = Block::new(3, client.tip().hash(), vec![coinbase(3)], 8).mine_in(algo, PowMode::BitCount);
+        assert_eq!(client.add_header(bit_count.header), Err(Error::DifficultyFail));
+    }
+}
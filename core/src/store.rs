@@ -0,0 +1,139 @@
+//! Storage backends for the UTXO set.
+//!
+//! [`crate::ledger::Ledger`] is generic over a [`UtxoStore`].  The default is
+//! an in-memory `HashMap`; with the `sled` feature [`SledStore`] keeps the set
+//! on disk so it need not fit in memory.
+//!
+//! Methods return owned outputs and `Result`s because disk-backed stores can
+//! neither hand out references into their storage nor guarantee I/O
+//! succeeds.
+
+use std::collections::HashMap;
+
+use crate::{ledger::UtxoKey, Result, TxOutput};
+
+/// Key-value store of unspent outputs.
+pub trait UtxoStore {
+    fn get(&self, key: &UtxoKey) -> Result<Option<TxOutput>>;
+
+    fn insert(&mut self, key: UtxoKey, output: TxOutput) -> Result<()>;
+
+    /// Removes `key`, returning the output if it was present.
+    fn remove(&mut self, key: &UtxoKey) -> Result<Option<TxOutput>>;
+
+    /// Iterates every entry in unspecified order.
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<(UtxoKey, TxOutput)>> + '_>;
+
+    fn contains(&self, key: &UtxoKey) -> Result<bool> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    /// Looks up every key in `keys`, answering in the same order with
+    /// `None` for missing ones.  Transaction validation fetches all inputs
+    /// through this, so backends with batched reads should override it; the
+    /// default simply calls [`UtxoStore::get`] per key.
+    fn get_many(&self, keys: &[UtxoKey]) -> Result<Vec<Option<TxOutput>>> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+}
+
+/// In-memory store; the default backend.
+impl UtxoStore for HashMap<UtxoKey, TxOutput> {
+    fn get(&self, key: &UtxoKey) -> Result<Option<TxOutput>> {
+        Ok(HashMap::get(self, key).cloned())
+    }
+
+    fn insert(&mut self, key: UtxoKey, output: TxOutput) -> Result<()> {
+        HashMap::insert(self, key, output);
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &UtxoKey) -> Result<Option<TxOutput>> {
+        Ok(HashMap::remove(self, key))
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<(UtxoKey, TxOutput)>> + '_> {
+        Box::new(HashMap::iter(self).map(|(k, v)| Ok((*k, v.clone()))))
+    }
+
+    fn contains(&self, key: &UtxoKey) -> Result<bool> {
+        Ok(self.contains_key(key))
+    }
+}
+
+#[cfg(feature = "sled")]
+pub use self::sled_store::SledStore;
+
+#[cfg(feature = "sled")]
+mod sled_store {
+    use super::UtxoStore;
+    use crate::{ledger::UtxoKey, Error, Result, TxOutput};
+
+    /// UTXO set persisted in a [`sled::Tree`].
+    ///
+    /// Keys are `prev_tx || output_index` (big-endian) and values are
+    /// `bincode`-encoded [`TxOutput`]s.
+    #[derive(Debug, Clone)]
+    pub struct SledStore {
+        tree: sled::Tree,
+    }
+
+    impl SledStore {
+        pub fn new(tree: sled::Tree) -> Self {
+            Self { tree }
+        }
+    }
+
+    impl From<sled::Error> for Error {
+        fn from(err: sled::Error) -> Self {
+            Error::Storage(err.to_string())
+        }
+    }
+
+    fn encode_key(key: &UtxoKey) -> [u8; 36] {
+        let mut out = [0u8; 36];
+        out[..32].copy_from_slice(&key.0);
+        out[32..].copy_from_slice(&key.1.to_be_bytes());
+        out
+    }
+
+    fn decode_key(bytes: &[u8]) -> Result<UtxoKey> {
+        if bytes.len() != 36 {
+            return Err(Error::Storage("malformed utxo key".into()));
+        }
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&bytes[..32]);
+        let index = u32::from_be_bytes(bytes[32..].try_into().expect("4 bytes"));
+        Ok((hash, index))
+    }
+
+    fn decode_output(bytes: &[u8]) -> Result<TxOutput> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    impl UtxoStore for SledStore {
+        fn get(&self, key: &UtxoKey) -> Result<Option<TxOutput>> {
+            self.tree.get(encode_key(key))?.map(|v| decode_output(&v)).transpose()
+        }
+
+        fn insert(&mut self, key: UtxoKey, output: TxOutput) -> Result<()> {
+            self.tree.insert(encode_key(&key), bincode::serialize(&output)?)?;
+            Ok(())
+        }
+
+        fn remove(&mut self, key: &UtxoKey) -> Result<Option<TxOutput>> {
+            self.tree.remove(encode_key(key))?.map(|v| decode_output(&v)).transpose()
+        }
+
+        fn iter(&self) -> Box<dyn Iterator<Item = Result<(UtxoKey, TxOutput)>> + '_> {
+            Box::new(self.tree.iter().map(|entry| {
+                let (k, v) = entry?;
+                Ok((decode_key(&k)?, decode_output(&v)?))
+            }))
+        }
+
+        fn contains(&self, key: &UtxoKey) -> Result<bool> {
+            Ok(self.tree.contains_key(encode_key(key))?)
+        }
+    }
+}
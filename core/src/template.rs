@@ -0,0 +1,216 @@
+//! Block templates for miners.
+//!
+//! A template is the next block a miner would attempt: it links to the
+//! ledger tip, carries a coinbase paying the block subsidy plus a selection of
+//! pending transactions, and has `nonce == 0`.  Miners call
+//! [`Block::mine`] on the result.
+//!
+//! Transactions after the coinbase are put in canonical order: highest
+//! fee-rate first, ties broken by ascending txid, with every parent placed
+//! before the children spending it.  Two miners selecting the same
+//! transactions therefore build the same block body.
+//!
+//! ```
+//! use obscura_core::{config::ConfigBuilder, ledger::Ledger, template::TemplateBuilder, Block, Transaction};
+//!
+//! let genesis = Block::new(1, [0u8; 32], vec![Transaction::coinbase(1, vec![], b"")], 0);
+//! let ledger = Ledger::with_config(&genesis, ConfigBuilder::new().difficulty(0).finish()).unwrap();
+//! let block = TemplateBuilder::new(&ledger, vec![1, 2, 3])
+//!     .coinbase_message(b"hello")
+//!     .finish()
+//!     .unwrap();
+//! assert_eq!(block.header.index, 2);
+//! ```
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    codec::{CanonicalCodec, Codec},
+    ledger::Ledger,
+    Block, Error, Hash, Result, Transaction, TxOutput,
+};
+
+/// Fluent builder producing an unmined [`Block`] on top of a ledger tip.
+pub struct TemplateBuilder<'a> {
+    ledger: &'a Ledger,
+    reward_pkh: Vec<u8>,
+    message: Vec<u8>,
+    transactions: Vec<Transaction>,
+}
+
+impl<'a> TemplateBuilder<'a> {
+    /// Starts a template extending `ledger`'s tip whose coinbase pays
+    /// `reward_pkh`.
+    pub fn new(ledger: &'a Ledger, reward_pkh: Vec<u8>) -> Self {
+        Self { ledger, reward_pkh, message: Vec::new(), transactions: Vec::new() }
+    }
+
+    /// Sets the miner message stamped into the coinbase.
+    pub fn coinbase_message(mut self, message: &[u8]) -> Self {
+        self.message = message.to_vec();
+        self
+    }
+
+    /// Sets the non-coinbase transactions to include.  [`Self::finish`]
+    /// reorders them canonically.
+    pub fn transactions(mut self, txs: Vec<Transaction>) -> Self {
+        self.transactions = txs;
+        self
+    }
+
+    /// Assembles the block.  Errors with [`Error::CoinbaseMessageTooLong`] if
+    /// the message exceeds the configured cap.
+    pub fn finish(self) -> Result<Block> {
+        let config = &self.ledger.config;
+        if self.message.len() > config.max_coinbase_message_len {
+            return Err(Error::CoinbaseMessageTooLong);
+        }
+        let height = self.ledger.height + 1;
+        let reward = TxOutput { value: config.block_subsidy(height), pubkey_hash: self.reward_pkh };
+        let mut txs = vec![Transaction::coinbase(height, vec![reward], &self.message)];
+        txs.extend(canonical_order(self.ledger, self.transactions));
+        Ok(Block::new(height, self.ledger.tip, txs, self.ledger.expected_difficulty()))
+    }
+}
+
+/// Sorts `txs` by descending fee-rate then ascending txid, holding each
+/// transaction back until the template transactions it spends are placed.
+///
+/// Inputs found neither in the ledger nor among `txs` count as zero value;
+/// such a template is invalid anyway and fails when applied.
+fn canonical_order(ledger: &Ledger, txs: Vec<Transaction>) -> Vec<Transaction> {
+    struct Candidate {
+        txid: Hash,
+        fee: u64,
+        size: u64,
+        tx: Transaction,
+    }
+
+    let outputs: HashMap<Hash, &[TxOutput]> = txs.iter().map(|tx| (tx.hash(), tx.outputs.as_slice())).collect();
+    let input_value = |tx: &Transaction| -> u64 {
+        tx.inputs
+            .iter()
+            .map(|i| {
+                let key = (i.prev_tx, i.output_index);
+                ledger.get_output_value(&key).ok().flatten().or_else(|| {
+                    outputs.get(&i.prev_tx)?.get(i.output_index as usize).map(|o| o.value)
+                })
+            })
+            .map(|v| v.unwrap_or(0))
+            .fold(0u64, u64::saturating_add)
+    };
+    let fees: Vec<u64> = txs
+        .iter()
+        .map(|tx| {
+            let out_value = tx.outputs.iter().fold(0u64, |acc, o| acc.saturating_add(o.value));
+            input_value(tx).saturating_sub(out_value)
+        })
+        .collect();
+    let mut candidates: Vec<Candidate> = txs
+        .into_iter()
+        .zip(fees)
+        .map(|(tx, fee)| Candidate {
+            txid: tx.hash(),
+            fee,
+            size: CanonicalCodec.encode_transaction(&tx).map_or(1, |b| b.len() as u64),
+            tx,
+        })
+        .collect();
+
+    // Higher fee-rate sorts first; compared by cross-multiplication.  After
+    // sorting, a candidate's index is its rank.
+    candidates.sort_by(|a, b| {
+        (b.fee as u128 * a.size as u128)
+            .cmp(&(a.fee as u128 * b.size as u128))
+            .then_with(|| a.txid.cmp(&b.txid))
+    });
+
+    // Kahn's algorithm: a candidate becomes ready once every template
+    // parent it spends from is placed; the best-ranked ready one goes next.
+    let rank: HashMap<Hash, usize> = candidates.iter().enumerate().map(|(i, c)| (c.txid, i)).collect();
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); candidates.len()];
+    let mut in_degree = vec![0usize; candidates.len()];
+    for (idx, c) in candidates.iter().enumerate() {
+        let parents: BTreeSet<usize> = c.tx.inputs.iter().filter_map(|i| rank.get(&i.prev_tx).copied()).collect();
+        for parent in parents.into_iter().filter(|&p| p != idx) {
+            children[parent].push(idx);
+            in_degree[idx] += 1;
+        }
+    }
+    let mut ready: BTreeSet<usize> = (0..candidates.len()).filter(|&i| in_degree[i] == 0).collect();
+    let mut order = Vec::with_capacity(candidates.len());
+    while let Some(next) = ready.pop_first() {
+        order.push(next);
+        for &child in &children[next] {
+            in_degree[child] -= 1;
+            if in_degree[child] == 0 {
+                ready.insert(child);
+            }
+        }
+    }
+    // A dependency cycle cannot be valid; emit the rest in rank order.
+    if order.len() < candidates.len() {
+        let placed: BTreeSet<usize> = order.iter().copied().collect();
+        order.extend((0..candidates.len()).filter(|i| !placed.contains(i)));
+    }
+
+    let mut slots: Vec<Option<Transaction>> = candidates.into_iter().map(|c| Some(c.tx)).collect();
+    order.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::ConfigBuilder, TEST_PUBKEY};
+
+    fn ledger(max_msg: usize) -> Ledger {
+        let genesis = Block::new(1, [0u8; 32], vec![Transaction::coinbase(1, vec![], b"")], 0);
+        let cfg = ConfigBuilder::new().difficulty(0).max_coinbase_message_len(max_msg).allow_unsigned(true).finish();
+        Ledger::with_config(&genesis, cfg).unwrap()
+    }
+
+    #[test]
+    fn coinbase_message_round_trips() {
+        let mut ledger = ledger(32);
+        let msg = b"The Times 03/Jan/2009 Chancellor";
+        let block = TemplateBuilder::new(&ledger, vec![7]).coinbase_message(msg).finish().unwrap();
+        ledger.apply_block(&block).unwrap();
+        assert_eq!(block.transactions[0].coinbase_message(), Some(&msg[..]));
+        assert_eq!(ledger.balance_for_pubkey_hash(&[7]), 50);
+    }
+
+    #[test]
+    fn over_long_message_rejected() {
+        let ledger = ledger(4);
+        let res = TemplateBuilder::new(&ledger, vec![7]).coinbase_message(b"12345").finish();
+        assert_eq!(res.err(), Some(Error::CoinbaseMessageTooLong));
+    }
+
+    #[test]
+    fn transactions_ordered_by_fee_rate_then_parents() {
+        use crate::TxInput;
+        let mut ledger = ledger(0);
+        // Fund three outputs of 10 at height 2.
+        let funding = Transaction::coinbase(2, vec![TxOutput { value: 10, pubkey_hash: vec![] }; 3], b"");
+        let block = Block::new(2, ledger.tip, vec![funding.clone()], 0);
+        ledger.apply_block(&block).unwrap();
+
+        let spend = |prev_tx, output_index, value| Transaction {
+            inputs: vec![TxInput { prev_tx, output_index, pubkey: TEST_PUBKEY.to_vec(), signature: vec![], scheme: 0 }],
+            outputs: vec![TxOutput { value, pubkey_hash: vec![] }],
+            metadata: None,
+        };
+        let low = spend(funding.hash(), 0, 9);
+        let high = spend(funding.hash(), 1, 5);
+        // Pays the most but must follow its low-fee parent.
+        let child = spend(low.hash(), 0, 0);
+        let txs = vec![child.clone(), low.clone(), high.clone()];
+        let block = TemplateBuilder::new(&ledger, vec![7]).transactions(txs).finish().unwrap();
+        let order: Vec<_> = block.transactions[1..].iter().map(Transaction::hash).collect();
+        assert_eq!(order, vec![high.hash(), low.hash(), child.hash()]);
+
+        let reversed = vec![high.clone(), low.clone(), child.clone()];
+        let again = TemplateBuilder::new(&ledger, vec![7]).transactions(reversed).finish().unwrap();
+        assert_eq!(again.header.merkle_root, block.header.merkle_root);
+    }
+}
@@ -0,0 +1,148 @@
+//! Transaction validation independent of UTXO storage.
+//!
+//! The checks here only need a way to look up the outputs a transaction
+//! spends, so the mempool, RPC and [`crate::ledger::Ledger`] can all share
+//! them regardless of where their UTXO view lives.
+
+use std::collections::HashMap;
+
+use crate::{config::Config, crypto, hash160, ledger::UtxoKey, sigcache::SigCache, Error, Hash, Result, Transaction, TxOutput};
+
+/// Fully validates a non-coinbase transaction: size limits, signatures,
+/// input order, uniqueness and existence, dust and value balance.  Returns
+/// the fee.
+///
+/// `lookup` resolves an outpoint to the unspent output it names, or `None`
+/// if it is unknown or spent.
+pub fn verify_transaction<'a>(
+    tx: &Transaction,
+    lookup: impl Fn(&UtxoKey) -> Option<&'a TxOutput>,
+    config: &Config,
+) -> Result<u64> {
+    tx.validate_structure(config)?;
+    verify_signatures(tx, config)?;
+    check_inputs(tx, lookup, config)
+}
+
+/// Same as [`verify_transaction`] but skips the signature checks if `cache`
+/// holds `tx`, and adds `tx` to it once they pass.
+pub fn verify_transaction_cached<'a>(
+    tx: &Transaction,
+    lookup: impl Fn(&UtxoKey) -> Option<&'a TxOutput>,
+    config: &Config,
+    cache: &SigCache,
+) -> Result<u64> {
+    tx.validate_structure(config)?;
+    cache.verify_with(tx, || verify_signatures(tx, config))?;
+    check_inputs(tx, lookup, config)
+}
+
+/// Verifies every input signature of `tx` under the scheme its tag names
+/// (see [`crypto::scheme`]).  Needs no UTXO state, which lets block
+/// validation run it in parallel.
+///
+/// Every pubkey must parse ([`Error::BadPubkey`] otherwise).  Inputs with an
+/// empty signature are accepted only under `config.allow_unsigned`.
+pub(crate) fn verify_signatures(tx: &Transaction, config: &Config) -> Result<()> {
+    let mut msg = None;
+    for inp in &tx.inputs {
+        let scheme = crypto::scheme(inp.scheme).ok_or(Error::UnknownSigScheme)?;
+        if !scheme.is_valid_pubkey(&inp.pubkey) {
+            return Err(Error::BadPubkey);
+        }
+        if inp.signature.is_empty() && config.allow_unsigned {
+            continue;
+        }
+        let msg = msg.get_or_insert_with(|| tx.signing_message());
+        if !scheme.verify(&inp.pubkey, msg, &inp.signature) {
+            return Err(Error::BadSignature);
+        }
+    }
+    Ok(())
+}
+
+/// Checks that every transaction spending an output created in the same
+/// block comes after the transaction creating it.
+pub(crate) fn check_tx_order(txs: &[Transaction]) -> Result<()> {
+    let positions: HashMap<Hash, usize> = txs.iter().enumerate().map(|(i, tx)| (tx.hash(), i)).collect();
+    for (idx, tx) in txs.iter().enumerate() {
+        if tx.inputs.iter().any(|i| positions.get(&i.prev_tx).is_some_and(|&p| p >= idx)) {
+            return Err(Error::UnsortedTransactions);
+        }
+    }
+    Ok(())
+}
+
+/// Checks that inputs are in canonical order and distinct, that every input
+/// exists and is owned by the input's key (its `pubkey` hashes to the
+/// output's `pubkey_hash`), and that outputs neither exceed the inputs nor
+/// fall below the dust threshold.  Signatures are *not* verified here.
+/// Returns the fee.
+///
+/// Unsigned inputs accepted under `config.allow_unsigned` are exempt from
+/// the ownership rule, as they prove nothing about the key anyway.
+pub(crate) fn check_inputs<'a>(
+    tx: &Transaction,
+    lookup: impl Fn(&UtxoKey) -> Option<&'a TxOutput>,
+    config: &Config,
+) -> Result<u64> {
+    if !tx.is_normalized() {
+        return Err(Error::UnsortedInputs);
+    }
+    tx.verify_no_duplicate_inputs()?;
+    let mut input_value = 0u64;
+    let mut output_value = 0u64;
+    for inp in &tx.inputs {
+        let prev_out = lookup(&(inp.prev_tx, inp.output_index)).ok_or(Error::MissingUtxo)?;
+        let unsigned = inp.signature.is_empty() && config.allow_unsigned;
+        if !unsigned && hash160(&inp.pubkey).as_slice() != prev_out.pubkey_hash.as_slice() {
+            return Err(Error::WrongOwner);
+        }
+        input_value = input_value.checked_add(prev_out.value).ok_or(Error::ValueOverflow)?;
+    }
+    for out in &tx.outputs {
+        // Zero-value outputs carry data only and are exempt from the dust rule.
+        if out.value > 0 && out.value < config.dust_threshold {
+            return Err(Error::DustOutput);
+        }
+        output_value = output_value.checked_add(out.value).ok_or(Error::ValueOverflow)?;
+    }
+    input_value.checked_sub(output_value).ok_or(Error::ValueOverflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TxInput, TEST_PUBKEY};
+    use std::collections::BTreeMap;
+
+    fn spend(prev: u8, value: u64) -> Transaction {
+        let input = TxInput { prev_tx: [prev; 32], output_index: 0, pubkey: TEST_PUBKEY.to_vec(), signature: vec![], scheme: 0 };
+        Transaction { inputs: vec![input], outputs: vec![TxOutput { value, pubkey_hash: vec![2] }], metadata: None }
+    }
+
+    #[test]
+    fn closure_backed_utxo_set() {
+        let utxos: BTreeMap<UtxoKey, TxOutput> =
+            [(([1; 32], 0), TxOutput { value: 40, pubkey_hash: vec![1] })].into_iter().collect();
+        let cfg = Config { allow_unsigned: true, ..Config::default() };
+        let lookup = |k: &UtxoKey| utxos.get(k);
+
+        assert_eq!(verify_transaction(&spend(1, 35), lookup, &cfg), Ok(5));
+        assert_eq!(verify_transaction(&spend(1, 41), lookup, &cfg), Err(Error::ValueOverflow));
+        assert_eq!(verify_transaction(&spend(2, 1), lookup, &cfg), Err(Error::MissingUtxo));
+    }
+
+    #[test]
+    fn spending_an_input_twice_is_rejected() {
+        let utxos: BTreeMap<UtxoKey, TxOutput> =
+            [(([1; 32], 0), TxOutput { value: 40, pubkey_hash: vec![1] })].into_iter().collect();
+        let cfg = Config { allow_unsigned: true, ..Config::default() };
+        let mut tx = spend(1, 70);
+        tx.inputs.push(tx.inputs[0].clone());
+        assert_eq!(tx.verify_no_duplicate_inputs(), Err(Error::DoubleSpend));
+        assert_eq!(verify_transaction(&tx, |k| utxos.get(k), &cfg), Err(Error::DoubleSpend));
+        tx.inputs.pop();
+        assert_eq!(tx.verify_no_duplicate_inputs(), Ok(()));
+    }
+}
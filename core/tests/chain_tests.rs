@@ -0,0 +1,191 @@
+use obscura_core::{
+    chain::{Blockchain, ReorgEvent}, config::{Config, ConfigBuilder}, Block, Error, Hash, Transaction, TxOutput,
+};
+
+fn coinbase(pkh: u8) -> Transaction {
+    Transaction {
+        inputs: vec![],
+        outputs: vec![TxOutput { value: 50, pubkey_hash: vec![pkh] }],
+        metadata: None,
+    }
+}
+
+fn three_block_chain() -> Blockchain {
+    let genesis = Block::new(1, [0u8; 32], vec![coinbase(1)], 0);
+    let mut chain = Blockchain::new(genesis).expect("genesis");
+    for h in 2..=3 {
+        let prev = chain.tip().unwrap().hash();
+        chain.add_block(Block::new(h, prev, vec![coinbase(h as u8)], 0)).expect("add block");
+    }
+    chain
+}
+
+#[test]
+fn lookup_by_height_and_hash() {
+    let chain = three_block_chain();
+    assert_eq!(chain.height(), 3);
+
+    let b2 = chain.get_block_by_height(2).expect("height 2");
+    assert_eq!(b2.header.index, 2);
+    let by_hash = chain.get_block_by_hash(&b2.hash()).expect("hash lookup");
+    assert_eq!(by_hash.hash(), b2.hash());
+}
+
+#[test]
+fn iter_from_tip_walks_back_to_genesis() {
+    let chain = three_block_chain();
+    let heights: Vec<u64> = chain.iter_from_tip().map(|b| b.header.index).collect();
+    assert_eq!(heights, vec![3, 2, 1]);
+    assert_eq!(chain.iter_from_tip().nth(1).unwrap().hash(), chain.get_block_by_height(2).unwrap().hash());
+}
+
+#[test]
+fn missing_height_or_hash_returns_none() {
+    let chain = three_block_chain();
+    assert!(chain.get_block_by_height(0).is_none());
+    assert!(chain.get_block_by_height(4).is_none());
+    let unknown: Hash = [0xEE; 32];
+    assert!(chain.get_block_by_hash(&unknown).is_none());
+}
+
+fn child(parent: &Block, pkh: u8) -> Block {
+    Block::new(parent.header.index + 1, parent.hash(), vec![coinbase(pkh)], 0)
+}
+
+fn shallow_reorg_chain() -> (Blockchain, Block) {
+    let cfg = ConfigBuilder::new().max_reorg_depth(1).finish();
+    let genesis = Block::new(1, [0u8; 32], vec![coinbase(1)], 0);
+    let mut chain = Blockchain::with_config(genesis.clone(), cfg).expect("genesis");
+    let a2 = child(&genesis, 2);
+    chain.add_block(a2.clone()).unwrap();
+    chain.add_block(child(&a2, 3)).unwrap();
+    (chain, genesis)
+}
+
+#[test]
+fn reorg_deeper_than_limit_is_rejected() {
+    let (mut chain, genesis) = shallow_reorg_chain();
+    let tip = chain.tip().unwrap().hash();
+
+    let b2 = child(&genesis, 20);
+    let b3 = child(&b2, 30);
+    let b4 = child(&b3, 40);
+    chain.add_block(b2).unwrap();
+    chain.add_block(b3).unwrap();
+    assert_eq!(chain.add_block(b4), Err(Error::ReorgTooDeep));
+    assert_eq!(chain.tip().unwrap().hash(), tip);
+    assert_eq!(chain.ledger.height, 3);
+}
+
+#[test]
+fn reorg_within_limit_is_accepted() {
+    let (mut chain, _) = shallow_reorg_chain();
+    let a2 = chain.get_block_by_height(2).unwrap().clone();
+    let a3 = chain.get_block_by_height(3).unwrap().hash();
+
+    let c3 = child(&a2, 31);
+    let c4 = child(&c3, 41);
+    chain.add_block(c3.clone()).unwrap();
+    assert_eq!(chain.tip().unwrap().hash(), a3);
+    chain.add_block(c4.clone()).unwrap();
+
+    assert_eq!(chain.height(), 4);
+    assert_eq!(chain.ledger.tip, c4.hash());
+    assert_eq!(chain.get_block_by_height(3).unwrap().hash(), c3.hash());
+    assert!(chain.get_block_by_hash(&a3).is_none());
+    assert_eq!(chain.ledger.balance_for_pubkey_hash(&[3]), 0);
+    assert_eq!(chain.ledger.balance_for_pubkey_hash(&[31]), 50);
+}
+
+#[test]
+fn confirmations_count_from_the_tip() {
+    let (mut chain, _) = shallow_reorg_chain();
+    let a2 = chain.get_block_by_height(2).unwrap().clone();
+    let in_parent = a2.transactions[0].hash();
+    let in_tip = chain.tip().unwrap().transactions[0].hash();
+    assert_eq!(chain.confirmations(&in_tip), Some(1));
+    assert_eq!(chain.confirmations(&in_parent), Some(2));
+    assert_eq!(chain.confirmations(&coinbase(99).hash()), None);
+
+    // Transactions only in the abandoned branch lose their confirmations.
+    let c3 = child(&a2, 31);
+    chain.add_block(c3.clone()).unwrap();
+    chain.add_block(child(&c3, 41)).unwrap();
+    assert_eq!(chain.confirmations(&in_tip), None);
+    assert_eq!(chain.confirmations(&c3.transactions[0].hash()), Some(2));
+    assert_eq!(chain.confirmations(&in_parent), Some(3));
+}
+
+#[test]
+fn reorg_reports_disconnected_and_connected_blocks() {
+    let (mut chain, _) = shallow_reorg_chain();
+    let events = chain.subscribe_reorgs();
+    let a2 = chain.get_block_by_height(2).unwrap().clone();
+    let a3 = chain.tip().unwrap().hash();
+
+    let c3 = child(&a2, 31);
+    let c4 = child(&c3, 41);
+    chain.add_block(c3.clone()).unwrap();
+    assert!(events.try_recv().is_err(), "storing a side branch is not a reorg");
+    chain.add_block(c4.clone()).unwrap();
+
+    let event = events.try_recv().unwrap();
+    assert_eq!(event, ReorgEvent { disconnected: vec![a3], connected: vec![c3.hash(), c4.hash()] });
+    chain.add_block(child(&c4, 51)).unwrap();
+    assert!(events.try_recv().is_err(), "extending the tip is not a reorg");
+}
+
+#[test]
+fn replay_names_the_invalid_block() {
+    let genesis = Block::new(1, [0u8; 32], vec![coinbase(1)], 0);
+    let b2 = child(&genesis, 2);
+    let b3 = child(&b2, 3);
+    let mut greedy = coinbase(4);
+    greedy.outputs[0].value = 51;
+    let bad = Block::new(4, b3.hash(), vec![greedy], 0);
+    let b5 = child(&bad, 5);
+
+    let chain = Blockchain::replay(genesis.clone(), vec![b2.clone(), b3.clone()], Config::default()).unwrap();
+    assert_eq!(chain.height(), 3);
+
+    let err = Blockchain::replay(genesis, vec![b2, b3, bad.clone(), b5], Config::default()).unwrap_err();
+    assert_eq!(err.block_hash, bad.hash());
+    assert_eq!(err.height, 4);
+    assert_eq!(err.source, Error::ExcessiveCoinbase);
+}
+
+#[test]
+fn load_rejects_config_of_another_chain() {
+    let config = ConfigBuilder::new().network("test").finish();
+    let mut chain = Blockchain::with_config(config.genesis_block(), config.clone()).unwrap();
+    let b2 = Block::new(2, chain.tip().unwrap().hash(), vec![coinbase(2)], chain.ledger.expected_difficulty()).mine();
+    chain.add_block(b2).unwrap();
+    let path = std::env::temp_dir().join(format!("obscura-chain-{}.dat", std::process::id()));
+    chain.save(&path).unwrap();
+
+    let loaded = Blockchain::load(&path, config).unwrap();
+    assert_eq!((loaded.height(), loaded.ledger.tip), (2, chain.ledger.tip));
+    let other = ConfigBuilder::new().network("main").finish();
+    assert_eq!(Blockchain::load(&path, other).unwrap_err(), Error::ConfigMismatch);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn reorg_notifies_ledger_subscribers_and_growth_hook() {
+    let (mut chain, _) = shallow_reorg_chain();
+    let blocks = chain.ledger.subscribe();
+    let deltas = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = deltas.clone();
+    chain.ledger.on_utxo_growth(move |delta| sink.lock().unwrap().push(delta));
+    let a2 = chain.get_block_by_height(2).unwrap().clone();
+
+    let c3 = child(&a2, 31);
+    let c4 = child(&c3, 41);
+    chain.add_block(c3.clone()).unwrap();
+    chain.add_block(c4.clone()).unwrap();
+
+    let connected: Vec<(u64, Hash)> = blocks.try_iter().map(|e| (e.height, e.block.hash())).collect();
+    assert_eq!(connected, vec![(3, c3.hash()), (4, c4.hash())]);
+    // The abandoned coinbase is removed, then one output per new block.
+    assert_eq!(*deltas.lock().unwrap(), vec![-1, 1, 1]);
+}
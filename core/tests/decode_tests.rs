@@ -0,0 +1,62 @@
+//! Malformed blocks must be rejected with an `Err`, never a panic.
+//!
+//! Mirrors the `block_decode` fuzz target deterministically: every
+//! truncation and single-byte corruption of a valid block is decoded and,
+//! when that succeeds, fully verified.
+#![cfg(feature = "serde")]
+
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+use obscura_core::{config::Config, Block, Transaction, TxInput, TxOutput};
+
+const SEED: &[u8] = include_bytes!("../fuzz/corpus/block_decode/valid_block");
+
+fn valid_block() -> Block {
+    let secret = SecretKey::from_bytes(&[7u8; 32]).unwrap();
+    let public = PublicKey::from(&secret);
+    let kp = Keypair { secret, public };
+    let coinbase = Transaction::coinbase(2, vec![TxOutput { value: 50, pubkey_hash: vec![1] }], b"seed");
+    let input = TxInput { prev_tx: [3u8; 32], output_index: 0, pubkey: public.as_bytes().to_vec(), signature: vec![], scheme: 0 };
+    let mut spend = Transaction { inputs: vec![input], outputs: vec![TxOutput { value: 9, pubkey_hash: vec![2] }], metadata: None };
+    spend.inputs[0].signature = kp.sign(&spend.signing_message()).to_bytes().to_vec();
+    let mut block = Block::new(2, [1u8; 32], vec![coinbase, spend], 0);
+    block.header.timestamp = 1_700_000_000;
+    block
+}
+
+fn decode_and_verify(bytes: &[u8]) {
+    if let Ok(block) = bincode::deserialize::<Block>(bytes) {
+        let _ = block.hash();
+        let _ = block.verify_full(&Config::default());
+    }
+}
+
+#[test]
+fn seed_is_a_valid_block() {
+    let block: Block = bincode::deserialize(SEED).unwrap();
+    assert_eq!(SEED, bincode::serialize(&valid_block()).unwrap());
+    assert!(block.verify_full(&Config::default()).is_ok());
+}
+
+#[test]
+fn corrupted_blocks_do_not_panic() {
+    let bytes = bincode::serialize(&valid_block()).unwrap();
+    for len in 0..bytes.len() {
+        decode_and_verify(&bytes[..len]);
+    }
+    for i in 0..bytes.len() {
+        for flip in [0x01, 0x80, 0xFF] {
+            let mut corrupt = bytes.clone();
+            corrupt[i] ^= flip;
+            decode_and_verify(&corrupt);
+        }
+    }
+}
+
+#[test]
+fn tampered_block_rejected() {
+    let mut block = valid_block();
+    block.transactions[1].outputs[0].value = 10;
+    assert!(block.verify_full(&Config::default()).is_err());
+    block.header.merkle_root = Block::calc_merkle_root(&block.transactions);
+    assert!(block.verify_full(&Config::default()).is_err(), "signature no longer matches");
+}
@@ -0,0 +1,39 @@
+use std::sync::Mutex;
+
+use log::{Level, Log, Metadata, Record};
+use obscura_core::{ledger::Ledger, Block, Transaction, TxOutput};
+
+/// Logger that records every message so tests can assert on them.
+struct Capture(Mutex<Vec<(Level, String)>>);
+
+impl Log for Capture {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.0.lock().unwrap().push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Capture = Capture(Mutex::new(Vec::new()));
+
+#[test]
+fn failed_apply_block_logs_warning_with_error_variant() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let coinbase = Transaction::coinbase(1, vec![TxOutput { value: 50, pubkey_hash: vec![1] }], b"");
+    let genesis = Block::new(1, [0u8; 32], vec![coinbase], 0);
+    let mut ledger = Ledger::new(&genesis).unwrap();
+    assert!(ledger.apply_block(&genesis).is_err());
+
+    let logs = LOGGER.0.lock().unwrap();
+    assert!(logs.iter().any(|(l, m)| *l == Level::Debug && m.starts_with("applied genesis")), "{logs:?}");
+    assert!(
+        logs.iter().any(|(l, m)| *l == Level::Warn && m.contains("rejected") && m.ends_with("DuplicateBlock")),
+        "{logs:?}"
+    );
+}
@@ -0,0 +1,140 @@
+//! Reference vectors for `pow::retarget`.
+//!
+//! Each expected target was computed by hand (arbitrary-precision
+//! `old_target * clamp(actual) / target_timespan`, truncated and saturated
+//! at 2^256 - 1) so the limb arithmetic is checked against an independent
+//! calculation.  Any change here is a consensus change.
+
+use obscura_core::{pow, Hash};
+
+struct Vector {
+    name: &'static str,
+    actual_timespan: u64,
+    target_timespan: u64,
+    old_target: &'static str,
+    expected: &'static str,
+}
+
+const VECTORS: &[Vector] = &[
+    Vector {
+        name: "no change when on schedule",
+        actual_timespan: 6000,
+        target_timespan: 6000,
+        old_target: "00000000ffff0000000000000000000000000000000000000000000000000000",
+        expected: "00000000ffff0000000000000000000000000000000000000000000000000000",
+    },
+    Vector {
+        name: "blocks twice as slow doubles the target",
+        actual_timespan: 12000,
+        target_timespan: 6000,
+        old_target: "00000000ffff0000000000000000000000000000000000000000000000000000",
+        expected: "00000001fffe0000000000000000000000000000000000000000000000000000",
+    },
+    Vector {
+        name: "blocks twice as fast halves the target",
+        actual_timespan: 3000,
+        target_timespan: 6000,
+        old_target: "00000000ffff0000000000000000000000000000000000000000000000000000",
+        expected: "000000007fff8000000000000000000000000000000000000000000000000000",
+    },
+    Vector {
+        name: "just inside the upper clamp",
+        actual_timespan: 23999,
+        target_timespan: 6000,
+        old_target: "00000000ffff0000000000000000000000000000000000000000000000000000",
+        expected: "00000003fff113d70a3d70a3d70a3d70a3d70a3d70a3d70a3d70a3d70a3d70a3",
+    },
+    Vector {
+        name: "exactly at the upper clamp",
+        actual_timespan: 24000,
+        target_timespan: 6000,
+        old_target: "00000000ffff0000000000000000000000000000000000000000000000000000",
+        expected: "00000003fffc0000000000000000000000000000000000000000000000000000",
+    },
+    Vector {
+        name: "above the upper clamp",
+        actual_timespan: 100000,
+        target_timespan: 6000,
+        old_target: "00000000ffff0000000000000000000000000000000000000000000000000000",
+        expected: "00000003fffc0000000000000000000000000000000000000000000000000000",
+    },
+    Vector {
+        name: "just inside the lower clamp",
+        actual_timespan: 1501,
+        target_timespan: 6000,
+        old_target: "00000000ffff0000000000000000000000000000000000000000000000000000",
+        expected: "00000000400aac28f5c28f5c28f5c28f5c28f5c28f5c28f5c28f5c28f5c28f5c",
+    },
+    Vector {
+        name: "exactly at the lower clamp",
+        actual_timespan: 1500,
+        target_timespan: 6000,
+        old_target: "00000000ffff0000000000000000000000000000000000000000000000000000",
+        expected: "000000003fffc000000000000000000000000000000000000000000000000000",
+    },
+    Vector {
+        name: "below the lower clamp",
+        actual_timespan: 1,
+        target_timespan: 6000,
+        old_target: "00000000ffff0000000000000000000000000000000000000000000000000000",
+        expected: "000000003fffc000000000000000000000000000000000000000000000000000",
+    },
+    Vector {
+        name: "division truncates",
+        actual_timespan: 2,
+        target_timespan: 3,
+        old_target: "0000000000000000000000000000000000000000000000000000000000000007",
+        expected: "0000000000000000000000000000000000000000000000000000000000000004",
+    },
+    Vector {
+        name: "saturates at the all-ones target",
+        actual_timespan: 24000,
+        target_timespan: 6000,
+        old_target: "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        expected: "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+    },
+    Vector {
+        name: "zero target timespan treated as one",
+        actual_timespan: 0,
+        target_timespan: 0,
+        old_target: "00000000ffff0000000000000000000000000000000000000000000000000000",
+        expected: "00000000ffff0000000000000000000000000000000000000000000000000000",
+    },
+    Vector {
+        name: "carry across limbs",
+        actual_timespan: 2,
+        target_timespan: 1,
+        old_target: "000000000000000000000000000000000000000000000000ffffffffffffffff",
+        expected: "000000000000000000000000000000000000000000000001fffffffffffffffe",
+    },
+    Vector {
+        name: "remainder across limbs",
+        actual_timespan: 1,
+        target_timespan: 3,
+        old_target: "0000000000000000000000000000000000000000000000010000000000000000",
+        expected: "0000000000000000000000000000000000000000000000005555555555555555",
+    },
+];
+
+fn parse(hex_target: &str) -> Hash {
+    hex::decode(hex_target).unwrap().try_into().unwrap()
+}
+
+#[test]
+fn retarget_matches_reference_vectors() {
+    let mut failures = Vec::new();
+    for v in VECTORS {
+        let computed = pow::retarget(&parse(v.old_target), v.actual_timespan, v.target_timespan);
+        if computed != parse(v.expected) {
+            failures.push(format!(
+                "{}: actual={} target_timespan={}\n  computed {}\n  expected {}",
+                v.name,
+                v.actual_timespan,
+                v.target_timespan,
+                hex::encode(computed),
+                v.expected,
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "retarget vectors failed:\n{}", failures.join("\n"));
+}
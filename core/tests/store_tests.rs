@@ -0,0 +1,143 @@
+//! Runs the same ledger scenarios against every `UtxoStore` backend.
+
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+use obscura_core::{
+    config::{Config, ConfigBuilder},
+    ledger::{Ledger, MemoryStore},
+    store::UtxoStore,
+    hash160, Block, Error, Hash, Transaction, TxInput, TxOutput,
+};
+
+fn keypair() -> Keypair {
+    let secret = SecretKey::from_bytes(&[7u8; 32]).unwrap();
+    let public = PublicKey::from(&secret);
+    Keypair { secret, public }
+}
+
+fn pay(value: u64, pkh: &[u8]) -> TxOutput {
+    TxOutput { value, pubkey_hash: pkh.to_vec() }
+}
+
+fn coinbase(outputs: Vec<TxOutput>) -> Transaction {
+    Transaction { inputs: vec![], outputs, metadata: None }
+}
+
+fn signed_spend(kp: &Keypair, prev_tx: Hash, outputs: Vec<TxOutput>) -> Transaction {
+    let input = TxInput { prev_tx, output_index: 0, pubkey: kp.public.as_bytes().to_vec(), signature: vec![], scheme: 0 };
+    let mut tx = Transaction { inputs: vec![input], outputs, metadata: None };
+    tx.inputs[0].signature = kp.sign(&tx.signing_message()).to_bytes().to_vec();
+    tx
+}
+
+/// Genesis paying 50 to the test key, then a block spending it with a fee
+/// of 5.
+fn spend_and_fee<S: UtxoStore>(store: S) {
+    let kp = keypair();
+    let owner = hash160(kp.public.as_bytes());
+    let cb = coinbase(vec![pay(50, &owner)]);
+    let genesis = Block::new(1, [0u8; 32], vec![cb.clone()], 0);
+    let mut ledger = Ledger::with_store(&genesis, Config::default(), store).unwrap();
+    assert_eq!(ledger.balance(&owner), Ok(50));
+
+    let spend = signed_spend(&kp, cb.hash(), vec![pay(30, &[2]), pay(15, &owner)]);
+    let block = Block::new(2, ledger.tip, vec![coinbase(vec![pay(55, &[3])]), spend.clone()], 0);
+    assert_eq!(ledger.total_fees_in_block(&block), Ok(5));
+    ledger.apply_block(&block).unwrap();
+
+    assert_eq!(ledger.height, 2);
+    assert_eq!(ledger.balance(&owner), Ok(15));
+    assert_eq!(ledger.balance(&[2]), Ok(30));
+    assert_eq!(ledger.balance(&[3]), Ok(55));
+    assert!(ledger.utxos.get(&(cb.hash(), 0)).unwrap().is_none());
+    assert_eq!(ledger.utxos.get(&(spend.hash(), 0)).unwrap().map(|o| o.value), Some(30));
+    assert_eq!(ledger.utxos.iter().count(), 3);
+
+    let again = Block::new(3, ledger.tip, vec![coinbase(vec![]), spend], 0);
+    assert_eq!(ledger.apply_block(&again), Err(Error::MissingUtxo));
+}
+
+fn bad_signature_rejected<S: UtxoStore>(store: S) {
+    let kp = keypair();
+    let owner = hash160(kp.public.as_bytes());
+    let cb = coinbase(vec![pay(50, &owner)]);
+    let genesis = Block::new(1, [0u8; 32], vec![cb.clone()], 0);
+    let mut ledger = Ledger::with_store(&genesis, Config::default(), store).unwrap();
+
+    let mut spend = signed_spend(&kp, cb.hash(), vec![pay(50, &[2])]);
+    spend.outputs[0].value = 49;
+    let block = Block::new(2, ledger.tip, vec![coinbase(vec![]), spend], 0);
+    assert_eq!(ledger.apply_block(&block), Err(Error::BadSignature));
+    assert_eq!(ledger.balance(&owner), Ok(50));
+}
+
+/// Applies a spend with undo, then rolls it back: the store must hand the
+/// spent output back and forget the created ones.
+fn undo_restores_spent_outputs<S: UtxoStore>(store: S) {
+    let kp = keypair();
+    let owner = hash160(kp.public.as_bytes());
+    let cb = coinbase(vec![pay(50, &owner)]);
+    let genesis = Block::new(1, [0u8; 32], vec![cb.clone()], 0);
+    let config = ConfigBuilder::new().coinbase_maturity(0).finish();
+    let mut ledger = Ledger::with_store(&genesis, config, store).unwrap();
+
+    let spend = signed_spend(&kp, cb.hash(), vec![pay(45, &[2])]);
+    let block = Block::new(2, ledger.tip, vec![coinbase(vec![pay(55, &[3])]), spend], 0);
+    let log = ledger.apply_block_with_undo(&block).unwrap();
+    assert_eq!(ledger.balance(&[2]), Ok(45));
+
+    ledger.rollback_to_height(1, &[log]).unwrap();
+    assert_eq!((ledger.height, ledger.tip), (1, genesis.hash()));
+    assert_eq!(ledger.balance(&owner), Ok(50));
+    assert_eq!(ledger.utxos.iter().count(), 1);
+    ledger.apply_block(&block).unwrap();
+    assert_eq!(ledger.balance(&[3]), Ok(55));
+}
+
+fn immature_coinbase_rejected<S: UtxoStore>(store: S) {
+    let kp = keypair();
+    let owner = hash160(kp.public.as_bytes());
+    let genesis = Block::new(1, [0u8; 32], vec![coinbase(vec![])], 0);
+    let config = ConfigBuilder::new().coinbase_maturity(3).finish();
+    let mut ledger = Ledger::with_store(&genesis, config, store).unwrap();
+    let cb = coinbase(vec![pay(50, &owner)]);
+    ledger.apply_block(&Block::new(2, ledger.tip, vec![cb.clone()], 0)).unwrap();
+
+    let spend = signed_spend(&kp, cb.hash(), vec![pay(50, &[2])]);
+    let block = Block::new(3, ledger.tip, vec![coinbase(vec![]), spend], 0);
+    assert_eq!(ledger.apply_block(&block), Err(Error::ImmatureCoinbase));
+    assert_eq!(ledger.balance(&owner), Ok(50));
+}
+
+fn get_many_preserves_order<S: UtxoStore>(mut store: S) {
+    let (a, b, missing) = (([1; 32], 0), ([2; 32], 1), ([3; 32], 0));
+    store.insert(a, pay(10, &[1])).unwrap();
+    store.insert(b, pay(20, &[2])).unwrap();
+    let values: Vec<Option<u64>> =
+        store.get_many(&[b, missing, a, b]).unwrap().into_iter().map(|o| o.map(|o| o.value)).collect();
+    assert_eq!(values, vec![Some(20), None, Some(10), Some(20)]);
+    assert_eq!(store.get_many(&[]), Ok(vec![]));
+}
+
+#[test]
+fn memory_store() {
+    spend_and_fee(MemoryStore::new());
+    bad_signature_rejected(MemoryStore::new());
+    undo_restores_spent_outputs(MemoryStore::new());
+    immature_coinbase_rejected(MemoryStore::new());
+    get_many_preserves_order(MemoryStore::new());
+}
+
+#[cfg(feature = "sled")]
+#[test]
+fn sled_store() {
+    use obscura_core::store::SledStore;
+    let open = || {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        SledStore::new(db.open_tree("utxos").unwrap())
+    };
+    spend_and_fee(open());
+    bad_signature_rejected(open());
+    undo_restores_spent_outputs(open());
+    immature_coinbase_rejected(open());
+    get_many_preserves_order(open());
+}
@@ -1,23 +1,50 @@
+use std::path::PathBuf;
+
 use eframe::{egui, NativeOptions};
+use obscura_core::{address::Address, config::Config, ledger::Ledger};
+
+mod status;
 
 fn main() -> eframe::Result<()> {
-    let app = WalletApp::default();
+    // Same default location the `obscura node` command saves to.
+    let path = std::env::args_os().nth(1).map_or_else(|| PathBuf::from("ledger.dat"), PathBuf::from);
+    let ledger = match Ledger::load_from_path(&path, Config::default()) {
+        Ok(ledger) => Some(ledger),
+        Err(err) => {
+            eprintln!("No ledger loaded from {}: {err}", path.display());
+            None
+        }
+    };
+    let app = WalletApp { ledger, ..WalletApp::default() };
     eframe::run_native("Obscura Wallet", NativeOptions::default(), Box::new(|_cc| Box::new(app)))
 }
 
 #[derive(Default)]
 struct WalletApp {
-    address: String,
+    address: Option<Address>,
     balance: u64,
     recipient: String,
     amount: String,
+    /// Chain state shown in the status bar, loaded from the node's snapshot
+    /// at startup.
+    ledger: Option<Ledger>,
 }
 
 impl eframe::App for WalletApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+            let line = match &self.ledger {
+                Some(ledger) => status::status_line(ledger),
+                None => "No ledger loaded".to_owned(),
+            };
+            ui.label(line);
+        });
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("Obscura GUI Wallet (placeholder)");
-            ui.label(format!("Address: {}", self.address));
+            match &self.address {
+                Some(address) => ui.label(format!("Address: {address}")),
+                None => ui.label("Address: none"),
+            };
             ui.label(format!("Balance: {}", self.balance));
             ui.separator();
             ui.heading("Send Transaction");
@@ -26,8 +53,15 @@ impl eframe::App for WalletApp {
             ui.label("Amount");
             ui.text_edit_singleline(&mut self.amount);
             if ui.button("Send").clicked() {
-                // TODO: call RPC to send
-                println!("Sending {} to {}", self.amount, self.recipient);
+                let network = match &self.ledger {
+                    Some(ledger) => ledger.config.network.clone(),
+                    None => Config::default().network,
+                };
+                match Address::parse(self.recipient.trim(), &network) {
+                    // TODO: call RPC to send
+                    Ok(to) => println!("Sending {} to {to}", self.amount),
+                    Err(err) => println!("Invalid recipient {}: {err}", self.recipient),
+                }
             }
         });
     }
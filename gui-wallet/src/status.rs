@@ -0,0 +1,35 @@
+//! Status bar contents, kept free of UI code so it can be tested headless.
+
+use obscura_core::ledger::Ledger;
+
+/// Number of tip-hash bytes shown in the status bar.
+const TIP_PREFIX: usize = 8;
+
+/// One-line summary of `ledger`: height, truncated tip hash and the
+/// difficulty the next block must meet.
+pub fn status_line(ledger: &Ledger) -> String {
+    format!(
+        "Height: {} | Tip: {}… | Difficulty: {}",
+        ledger.height,
+        hex::encode(&ledger.tip[..TIP_PREFIX]),
+        ledger.expected_difficulty()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use obscura_core::{config::ConfigBuilder, Block, Transaction};
+
+    #[test]
+    fn shows_height_tip_and_difficulty() {
+        let genesis = Block::new(1, [0u8; 32], vec![Transaction::coinbase(1, vec![], b"")], 0);
+        let cfg = ConfigBuilder::new().difficulty(0).finish();
+        let mut ledger = Ledger::with_config(&genesis, cfg).unwrap();
+        let next = Block::new(2, ledger.tip, vec![Transaction::coinbase(2, vec![], b"")], 0);
+        ledger.apply_block(&next).unwrap();
+
+        let tip = hex::encode(next.hash());
+        assert_eq!(status_line(&ledger), format!("Height: 2 | Tip: {}… | Difficulty: 0", &tip[..16]));
+    }
+}
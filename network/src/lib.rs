@@ -1,5 +1,13 @@
 //! Networking layer using libp2p + tokio.
 
+pub mod message;
+pub mod relay;
+pub mod sync;
+
+pub use message::{handle_inv, Message};
+pub use relay::{handle_new_tx, RateLimiter, RelayDecision};
+pub use sync::{parallel_download, Peer};
+
 pub fn init() {
     // TODO: implement swarm setup
 }
@@ -0,0 +1,52 @@
+//! Peer-to-peer wire messages.
+//!
+//! Transactions are gossiped in two steps to save bandwidth: a node
+//! announces the txids it has with [`Message::InvTx`], and peers fetch only
+//! the unknown ones with [`Message::GetTx`], answered by [`Message::Tx`].
+
+use std::collections::HashSet;
+
+use obscura_core::{mempool::Mempool, Hash, Transaction};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Message {
+    /// Announces transactions the sender has pooled.
+    InvTx(Vec<Hash>),
+    /// Requests the full transaction with the given txid.
+    GetTx(Hash),
+    /// A full transaction, in reply to [`Message::GetTx`].
+    Tx(Transaction),
+}
+
+/// Returns the txids from an [`Message::InvTx`] announcement that are not in
+/// `mempool` and should be requested with [`Message::GetTx`], in announcement
+/// order and without duplicates.
+pub fn handle_inv(inv: &[Hash], mempool: &Mempool) -> Vec<Hash> {
+    let mut seen = HashSet::new();
+    inv.iter()
+        .filter(|txid| !mempool.contains(txid) && seen.insert(**txid))
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use obscura_core::{config::ConfigBuilder, ledger::Ledger, Block, TxInput, TxOutput, TEST_PUBKEY};
+
+    #[test]
+    fn requests_only_unknown_txids() {
+        let coinbase = Transaction::coinbase(1, vec![TxOutput { value: 50, pubkey_hash: vec![1] }], b"");
+        let genesis = Block::new(1, [0u8; 32], vec![coinbase.clone()], 0);
+        let ledger = Ledger::with_config(&genesis, ConfigBuilder::new().allow_unsigned(true).finish()).unwrap();
+        let input = TxInput { prev_tx: coinbase.hash(), output_index: 0, pubkey: TEST_PUBKEY.to_vec(), signature: vec![], scheme: 0 };
+        let tx = Transaction { inputs: vec![input], outputs: vec![TxOutput { value: 45, pubkey_hash: vec![2] }], metadata: None };
+        let mut mempool = Mempool::new();
+        let known = mempool.add(tx, &ledger).unwrap();
+
+        let (a, b) = ([1u8; 32], [2u8; 32]);
+        assert_eq!(handle_inv(&[a, known, b, a], &mempool), vec![a, b]);
+        assert!(handle_inv(&[known], &mempool).is_empty());
+    }
+}
@@ -0,0 +1,115 @@
+//! Admission of transactions relayed by peers.
+//!
+//! Every peer connection owns a [`RateLimiter`]; [`handle_new_tx`] charges
+//! it one token per transaction before doing any validation work, so a peer
+//! flooding transactions is cut off cheaply while well-behaved peers are
+//! unaffected.
+
+use std::time::Instant;
+
+use obscura_core::{ledger::Ledger, mempool::Mempool, Error, Transaction};
+
+/// Token bucket allowing `per_second` events per second on average and
+/// bursts of up to `per_second` at once.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    per_second: u32,
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    /// A full bucket.
+    pub fn new(per_second: u32) -> Self {
+        Self { per_second, tokens: per_second as f64, last: Instant::now() }
+    }
+
+    /// Takes a token if one is available.
+    pub fn try_acquire(&mut self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    /// Same as [`RateLimiter::try_acquire`] with an explicit clock, after
+    /// refilling for the time elapsed since the previous call.
+    pub fn try_acquire_at(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last);
+        self.last = self.last.max(now);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.per_second as f64).min(self.per_second as f64);
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// What to do with a transaction a peer sent, see [`handle_new_tx`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelayDecision {
+    /// Accepted into the mempool; announce it to other peers.
+    Relay,
+    /// Already pooled; nothing to do.
+    AlreadyKnown,
+    /// Failed validation.
+    Rejected(Error),
+    /// The peer exceeded its rate limit; the transaction was not examined.
+    RateLimited,
+}
+
+/// Admits `tx` from the peer owning `limiter` into `mempool`.
+pub fn handle_new_tx(tx: Transaction, limiter: &mut RateLimiter, mempool: &mut Mempool, ledger: &Ledger) -> RelayDecision {
+    handle_new_tx_at(tx, limiter, mempool, ledger, Instant::now())
+}
+
+fn handle_new_tx_at(
+    tx: Transaction,
+    limiter: &mut RateLimiter,
+    mempool: &mut Mempool,
+    ledger: &Ledger,
+    now: Instant,
+) -> RelayDecision {
+    if !limiter.try_acquire_at(now) {
+        return RelayDecision::RateLimited;
+    }
+    if mempool.contains(&tx.hash()) {
+        return RelayDecision::AlreadyKnown;
+    }
+    match mempool.add(tx, ledger) {
+        Ok(_) => RelayDecision::Relay,
+        Err(err) => RelayDecision::Rejected(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use obscura_core::{config::ConfigBuilder, Block, TxInput, TxOutput, TEST_PUBKEY};
+
+    #[test]
+    fn bursts_are_rate_limited_until_the_bucket_refills() {
+        let coinbase = Transaction::coinbase(1, (1..=5).map(|v| TxOutput { value: 10 * v, pubkey_hash: vec![1] }).collect(), b"");
+        let genesis = Block::new(1, [0u8; 32], vec![coinbase.clone()], 0);
+        let ledger = Ledger::with_config(&genesis, ConfigBuilder::new().allow_unsigned(true).finish()).unwrap();
+        let spend = |index: u32| {
+            let input = TxInput { prev_tx: coinbase.hash(), output_index: index, pubkey: TEST_PUBKEY.to_vec(), signature: vec![], scheme: 0 };
+            Transaction { inputs: vec![input], outputs: vec![TxOutput { value: 5, pubkey_hash: vec![2] }], metadata: None }
+        };
+        let mut mempool = Mempool::new();
+        let mut limiter = RateLimiter::new(2);
+        let t0 = Instant::now();
+
+        let mut relay = |index, now| handle_new_tx_at(spend(index), &mut limiter, &mut mempool, &ledger, now);
+        assert_eq!(relay(0, t0), RelayDecision::Relay);
+        assert_eq!(relay(0, t0), RelayDecision::AlreadyKnown);
+        assert_eq!(relay(1, t0), RelayDecision::RateLimited);
+        assert_eq!(relay(1, t0 + Duration::from_millis(250)), RelayDecision::RateLimited);
+        assert_eq!(relay(1, t0 + Duration::from_millis(500)), RelayDecision::Relay);
+        // A long pause refills only up to the burst size.
+        let later = t0 + Duration::from_secs(10);
+        assert_eq!(relay(2, later), RelayDecision::Relay);
+        assert_eq!(relay(3, later), RelayDecision::Relay);
+        assert_eq!(relay(4, later), RelayDecision::RateLimited);
+        assert!(matches!(relay(9, later + Duration::from_secs(1)), RelayDecision::Rejected(Error::MissingUtxo)));
+    }
+}
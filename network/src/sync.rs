@@ -0,0 +1,210 @@
+//! Block download during initial sync.
+//!
+//! [`parallel_download`] keeps up to `window` blocks in flight, spread
+//! round-robin over every [`Peer`] that claims to have them, and fetches
+//! from all peers concurrently.  Replies arrive in any order; they wait in a
+//! reorder buffer keyed by height until every block below them has been
+//! applied, so the ledger still sees the chain strictly in order.
+
+use std::collections::BTreeMap;
+use std::thread;
+
+use obscura_core::{config::Config, ledger::Ledger, Block, Error, Result};
+
+/// A remote node blocks can be requested from.
+pub trait Peer {
+    /// Height of the best chain the peer advertises.
+    fn best_height(&self) -> u64;
+
+    /// Fetches the peer's block at `height`, or `None` if it cannot serve it.
+    fn get_block(&mut self, height: u64) -> Option<Block>;
+}
+
+/// Downloads and applies blocks until `ledger` reaches the best height any
+/// of `peers` advertises, returning the final height.
+///
+/// Each round requests the next `window` heights not yet buffered.  Blocks
+/// failing [`Block::verify_full`] under `config` or carrying the wrong
+/// height are discarded and requested again, from the next peer in turn.
+/// A peer whose block the ledger still rejects is dropped for the rest of
+/// the sync, along with everything else it served, and the height is
+/// requested again from the remaining peers.  Errors with [`Error::Other`]
+/// if a full rotation of peers brings no progress or every peer is dropped.
+pub fn parallel_download<P: Peer + Send>(
+    peers: &mut [P],
+    ledger: &mut Ledger,
+    config: &Config,
+    window: usize,
+) -> Result<u64> {
+    let mut dropped = vec![false; peers.len()];
+    // Buffered blocks, keyed by height, with the index of the peer that
+    // served them.
+    let mut buffer: BTreeMap<u64, (usize, Block)> = BTreeMap::new();
+    let mut round = 0usize;
+    let mut stalled = 0usize;
+    loop {
+        let live = |i: &usize| !dropped[*i];
+        let Some(target) = (0..peers.len()).filter(live).map(|i| peers[i].best_height()).max() else {
+            if dropped.contains(&true) {
+                return Err(Error::Other("sync failed: every peer served an invalid block"));
+            }
+            break;
+        };
+        if ledger.height >= target {
+            break;
+        }
+        let next = ledger.height + 1;
+        let end = target.min(next + window.max(1) as u64 - 1);
+
+        // Assign every missing height in the window to a peer that has it,
+        // rotating the assignment each round so a peer that failed to serve
+        // a height is not asked for it again right away.
+        let mut requests: Vec<Vec<u64>> = vec![Vec::new(); peers.len()];
+        for height in (next..=end).filter(|h| !buffer.contains_key(h)) {
+            let holders: Vec<usize> =
+                (0..peers.len()).filter(live).filter(|&i| peers[i].best_height() >= height).collect();
+            if !holders.is_empty() {
+                requests[holders[(height as usize + round) % holders.len()]].push(height);
+            }
+        }
+        let replies: Vec<(usize, u64, Block)> = thread::scope(|scope| {
+            let handles: Vec<_> = peers
+                .iter_mut()
+                .zip(requests)
+                .enumerate()
+                .filter(|(_, (_, heights))| !heights.is_empty())
+                .map(|(i, (peer, heights))| {
+                    scope.spawn(move || {
+                        heights.into_iter().filter_map(|h| Some((i, h, peer.get_block(h)?))).collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles.into_iter().flat_map(|h| h.join().expect("peer thread panicked")).collect()
+        });
+        for (peer, height, block) in replies {
+            if block.header.index == height && block.verify_full(config).is_ok() {
+                buffer.insert(height, (peer, block));
+            }
+        }
+
+        let before = ledger.height;
+        while let Some((peer, block)) = buffer.remove(&(ledger.height + 1)) {
+            if ledger.apply(&block).is_err() {
+                dropped[peer] = true;
+                buffer.retain(|_, (p, _)| *p != peer);
+                break;
+            }
+        }
+        if ledger.height == before {
+            stalled += 1;
+            if stalled > peers.len() {
+                return Err(Error::Other("sync stalled: no peer served the next block"));
+            }
+        } else {
+            stalled = 0;
+        }
+        round += 1;
+    }
+    Ok(ledger.height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use obscura_core::{config::ConfigBuilder, Hash, Transaction, TxOutput};
+    use std::sync::{Arc, Mutex};
+
+    /// Serves `chain[..best]`, skipping heights `skip` rejects.  Heights in
+    /// `defer` are refused the first time they are asked for, so they
+    /// arrive in a later round than the heights above them.
+    struct FakePeer {
+        chain: Vec<Block>,
+        best: u64,
+        skip: fn(u64) -> bool,
+        defer: Vec<u64>,
+        arrivals: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl Peer for FakePeer {
+        fn best_height(&self) -> u64 {
+            self.best
+        }
+
+        fn get_block(&mut self, height: u64) -> Option<Block> {
+            if height > self.best || (self.skip)(height) {
+                return None;
+            }
+            if let Some(i) = self.defer.iter().position(|&h| h == height) {
+                self.defer.remove(i);
+                return None;
+            }
+            self.arrivals.lock().unwrap().push(height);
+            Some(self.chain[height as usize - 1].clone())
+        }
+    }
+
+    fn coinbase_block(h: u64, prev: Hash, value: u64) -> Block {
+        let coinbase = Transaction::coinbase(h, vec![TxOutput { value, pubkey_hash: vec![h as u8] }], b"");
+        let root = Block::calc_merkle_root(std::slice::from_ref(&coinbase));
+        Block::new_with_root(h, prev, vec![coinbase], 0, root, 1_700_000_000 + 60 * h)
+    }
+
+    fn chain(len: u64) -> Vec<Block> {
+        let mut blocks = vec![Block::new(1, [0; 32], vec![Transaction::coinbase(1, vec![], b"")], 0)];
+        for h in 2..=len {
+            blocks.push(coinbase_block(h, blocks.last().unwrap().hash(), 50));
+        }
+        blocks
+    }
+
+    #[test]
+    fn out_of_order_replies_are_applied_in_height_order() {
+        let config = ConfigBuilder::new().difficulty(0).finish();
+        let blocks = chain(12);
+        let mut ledger = Ledger::with_config(&blocks[0], config.clone()).unwrap();
+        let arrivals = Arc::new(Mutex::new(Vec::new()));
+        let peer = |best, skip, defer| FakePeer { chain: blocks.clone(), best, skip, defer, arrivals: arrivals.clone() };
+        // Height 2 goes to peer 2, then peer 0, and both put it off; peer 1
+        // serves it in the third round, after 3, 4 and 5 came in the first.
+        let mut peers = [peer(12, |_| false, vec![2]), peer(12, |h| h % 2 == 1, vec![]), peer(7, |_| false, vec![2])];
+
+        assert_eq!(parallel_download(&mut peers, &mut ledger, &config, 4), Ok(12));
+        assert_eq!(ledger.tip, blocks[11].hash());
+        let arrivals = arrivals.lock().unwrap();
+        let position = |height| arrivals.iter().position(|&h| h == height).unwrap();
+        assert!((3..=5).all(|h| position(h) < position(2)), "{arrivals:?}");
+    }
+
+    #[test]
+    fn stalls_when_no_peer_has_the_next_block() {
+        let config = ConfigBuilder::new().difficulty(0).finish();
+        let blocks = chain(5);
+        let mut ledger = Ledger::with_config(&blocks[0], config.clone()).unwrap();
+        let arrivals = Arc::new(Mutex::new(Vec::new()));
+        let mut peers = [FakePeer { chain: blocks, best: 5, skip: |h| h == 3, defer: vec![], arrivals }];
+        assert!(parallel_download(&mut peers, &mut ledger, &config, 2).is_err());
+        assert_eq!(ledger.height, 2);
+    }
+
+    #[test]
+    fn peer_serving_a_rejected_block_is_dropped() {
+        let config = ConfigBuilder::new().difficulty(0).finish();
+        let blocks = chain(6);
+        let mut forged = blocks.clone();
+        // Well-formed, so it passes `verify_full`, but pays too much.
+        forged[2] = coinbase_block(3, blocks[1].hash(), 1_000);
+        let mut ledger = Ledger::with_config(&blocks[0], config.clone()).unwrap();
+        let arrivals = Arc::new(Mutex::new(Vec::new()));
+        let peer = |chain| FakePeer { chain, best: 6, skip: |_| false, defer: vec![], arrivals: arrivals.clone() };
+        // Round-robin gives height 3 to the forger first.
+        let mut peers = [peer(blocks.clone()), peer(forged.clone())];
+
+        assert_eq!(parallel_download(&mut peers, &mut ledger, &config, 5), Ok(6));
+        assert_eq!(ledger.tip, blocks[5].hash());
+
+        let mut ledger = Ledger::with_config(&blocks[0], config.clone()).unwrap();
+        let mut peers = [peer(forged.clone()), peer(forged)];
+        assert!(parallel_download(&mut peers, &mut ledger, &config, 5).is_err());
+        assert_eq!(ledger.height, 2);
+    }
+}
@@ -1,4 +1,11 @@
 //! JSON-RPC server stub using jsonrpsee.
+//!
+//! [`mining`] provides the `getblocktemplate`/`submitblock` methods
+//! external miners use.
+
+pub mod mining;
+
+pub use mining::{mining_module, BlockTemplate, MiningContext};
 
 pub fn start_server() {
     // TODO: implement RPC server
@@ -0,0 +1,171 @@
+//! Mining RPCs for external miners.
+//!
+//! * `getblocktemplate [reward_pkh]` returns the unmined next block as a
+//!   [`BlockTemplate`]: header fields, the PoW target and the transactions
+//!   (coinbase first) built by [`TemplateBuilder`] from the mempool.
+//! * `submitblock [block]` decodes a solved block, adds it to the chain and
+//!   drops its transactions from the mempool, returning the block hash.
+//!
+//! Hashes, the reward pubkey hash, transactions and blocks are hex encoded;
+//! transactions and blocks use the network's consensus codec
+//! ([`obscura_core::config::Config::codec`]).
+
+use std::sync::{Arc, RwLock};
+
+use jsonrpsee::types::{error::CALL_EXECUTION_FAILED_CODE, ErrorObject, ErrorObjectOwned};
+use jsonrpsee::RpcModule;
+use serde::{Deserialize, Serialize};
+
+use obscura_core::{
+    chain::Blockchain, codec::CodecKind, mempool::Mempool, pow::PowMode, template::TemplateBuilder, Block, BlockHeader, Error,
+    Result,
+};
+
+/// Shared node state the mining methods operate on.
+#[derive(Clone)]
+pub struct MiningContext {
+    pub chain: Arc<RwLock<Blockchain>>,
+    pub mempool: Arc<RwLock<Mempool>>,
+}
+
+/// The unmined next block, as returned by `getblocktemplate`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockTemplate {
+    pub version: u16,
+    pub height: u64,
+    pub prev_hash: String,
+    pub merkle_root: String,
+    pub timestamp: u64,
+    pub difficulty: u32,
+    /// Largest header PoW hash that satisfies `difficulty`.
+    pub target: String,
+    /// Encoded transactions, coinbase first.
+    pub transactions: Vec<String>,
+}
+
+impl BlockTemplate {
+    fn from_block(block: &Block, codec: CodecKind, mode: PowMode) -> Result<Self> {
+        let h = &block.header;
+        let transactions = block
+            .transactions
+            .iter()
+            .map(|tx| codec.codec().encode_transaction(tx).map(hex::encode))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            version: h.version,
+            height: h.index,
+            prev_hash: hex::encode(h.prev_hash),
+            merkle_root: hex::encode(h.merkle_root),
+            timestamp: h.timestamp,
+            difficulty: h.difficulty,
+            target: hex::encode(mode.target(h.difficulty)),
+            transactions,
+        })
+    }
+
+    /// Rebuilds the block (with nonce 0) a miner searches over.
+    pub fn to_block(&self, codec: CodecKind) -> Result<Block> {
+        let hash = |s: &str| -> Result<_> {
+            let mut out = [0u8; 32];
+            hex::decode_to_slice(s, &mut out).map_err(|_| Error::Other("malformed hex"))?;
+            Ok(out)
+        };
+        let transactions = self
+            .transactions
+            .iter()
+            .map(|tx| codec.codec().decode_transaction(&hex::decode(tx).map_err(|_| Error::Other("malformed hex"))?))
+            .collect::<Result<_>>()?;
+        let header = BlockHeader {
+            version: self.version,
+            index: self.height,
+            timestamp: self.timestamp,
+            prev_hash: hash(&self.prev_hash)?,
+            merkle_root: hash(&self.merkle_root)?,
+            nonce: 0,
+            difficulty: self.difficulty,
+            utxo_commitment: None,
+        };
+        Ok(Block { header, transactions })
+    }
+}
+
+/// Builds the template for the block after the current tip, paying the
+/// coinbase to `reward_pkh` and including every pooled transaction.
+pub fn get_block_template(ctx: &MiningContext, reward_pkh: Vec<u8>) -> Result<BlockTemplate> {
+    let chain = ctx.chain.read().unwrap_or_else(|e| e.into_inner());
+    let txs = ctx.mempool.read().unwrap_or_else(|e| e.into_inner()).transactions().cloned().collect();
+    let block = TemplateBuilder::new(&chain.ledger, reward_pkh).transactions(txs).finish()?;
+    BlockTemplate::from_block(&block, chain.ledger.config.codec, chain.ledger.config.pow_mode)
+}
+
+/// Decodes, validates and connects a solved block, then removes its
+/// transactions from the mempool.  Returns the block hash.
+pub fn submit_block(ctx: &MiningContext, encoded: &[u8]) -> Result<[u8; 32]> {
+    let mut chain = ctx.chain.write().unwrap_or_else(|e| e.into_inner());
+    let block = chain.ledger.config.codec.codec().decode_block(encoded)?;
+    let hash = block.hash();
+    let txids: Vec<_> = block.transactions.iter().map(|tx| tx.hash()).collect();
+    chain.add_block(block)?;
+    let mut mempool = ctx.mempool.write().unwrap_or_else(|e| e.into_inner());
+    for txid in &txids {
+        mempool.remove(txid);
+    }
+    Ok(hash)
+}
+
+fn rpc_error(err: impl ToString) -> ErrorObjectOwned {
+    ErrorObject::owned(CALL_EXECUTION_FAILED_CODE, err.to_string(), None::<()>)
+}
+
+/// Registers `getblocktemplate` and `submitblock` on a module over `ctx`.
+pub fn mining_module(ctx: MiningContext) -> RpcModule<MiningContext> {
+    let mut module = RpcModule::new(ctx);
+    module
+        .register_method("getblocktemplate", |params, ctx, _| {
+            let pkh = hex::decode(params.one::<String>()?).map_err(rpc_error)?;
+            get_block_template(ctx, pkh).map_err(rpc_error)
+        })
+        .expect("unique method name");
+    module
+        .register_method("submitblock", |params, ctx, _| {
+            let block = hex::decode(params.one::<String>()?).map_err(rpc_error)?;
+            submit_block(ctx, &block).map(hex::encode).map_err(rpc_error)
+        })
+        .expect("unique method name");
+    module
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use obscura_core::{config::ConfigBuilder, Transaction, TxInput, TxOutput, TEST_PUBKEY};
+
+    #[tokio::test]
+    async fn template_mined_and_submitted_advances_chain() {
+        let cfg = ConfigBuilder::new().difficulty(4).allow_unsigned(true).finish();
+        let coinbase = Transaction::coinbase(1, vec![TxOutput { value: 50, pubkey_hash: vec![1] }], b"");
+        let genesis = Block::new(1, [0u8; 32], vec![coinbase.clone()], 4).mine();
+        let chain = Blockchain::with_config(genesis, cfg.clone()).unwrap();
+        let mut mempool = Mempool::new();
+        let input = TxInput { prev_tx: coinbase.hash(), output_index: 0, pubkey: TEST_PUBKEY.to_vec(), signature: vec![], scheme: 0 };
+        let spend = Transaction { inputs: vec![input], outputs: vec![TxOutput { value: 45, pubkey_hash: vec![2] }], metadata: None };
+        mempool.add(spend.clone(), &chain.ledger).unwrap();
+        let ctx = MiningContext { chain: Arc::new(RwLock::new(chain)), mempool: Arc::new(RwLock::new(mempool)) };
+        let module = mining_module(ctx.clone());
+
+        let template: BlockTemplate = module.call("getblocktemplate", ["aa"]).await.unwrap();
+        assert_eq!((template.height, template.difficulty), (2, 4));
+        assert_eq!(template.transactions.len(), 2);
+
+        let block = template.to_block(cfg.codec).unwrap().mine();
+        assert_eq!(block.transactions[1], spend);
+        let encoded = hex::encode(cfg.codec.codec().encode_block(&block).unwrap());
+        let hash: String = module.call("submitblock", [encoded.clone()]).await.unwrap();
+
+        assert_eq!(hash, hex::encode(block.hash()));
+        assert_eq!(ctx.chain.read().unwrap().height(), 2);
+        assert_eq!(ctx.chain.read().unwrap().ledger.balance_for_pubkey_hash(&[0xaa]), 50);
+        assert!(ctx.mempool.read().unwrap().is_empty());
+        assert!(module.call::<_, String>("submitblock", [encoded]).await.is_err(), "duplicate block");
+    }
+}
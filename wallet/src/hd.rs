@@ -0,0 +1,63 @@
+//! Deterministic key derivation.
+//!
+//! An [`HdWallet`] derives any number of keypairs from one 32-byte seed, so
+//! backing up the seed backs up every address.  Child `i` uses the secret
+//! `hash256("obscura-hd" || seed || i)` with `i` big-endian.  This is a
+//! simple non-hierarchical scheme, not BIP32/SLIP-10 compatible.
+
+use ed25519_dalek::{Keypair, PublicKey, SecretKey};
+use obscura_core::hash256;
+use rand::{rngs::OsRng, RngCore};
+
+const DOMAIN: &[u8] = b"obscura-hd";
+
+#[derive(Clone)]
+pub struct HdWallet {
+    seed: [u8; 32],
+    next_index: u32,
+}
+
+impl HdWallet {
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        Self { seed, next_index: 0 }
+    }
+
+    /// Resumes a wallet that has already handed out the keys below
+    /// `next_index`, e.g. from persisted state.
+    pub fn resume(seed: [u8; 32], next_index: u32) -> Self {
+        Self { seed, next_index }
+    }
+
+    /// Creates a wallet from a fresh random seed.
+    pub fn generate() -> Self {
+        let mut seed = [0u8; 32];
+        OsRng.fill_bytes(&mut seed);
+        Self::from_seed(seed)
+    }
+
+    pub fn seed(&self) -> &[u8; 32] {
+        &self.seed
+    }
+
+    /// Index [`HdWallet::next_keypair`] will derive next.
+    pub fn next_index(&self) -> u32 {
+        self.next_index
+    }
+
+    /// Derives the keypair at `index`.
+    pub fn derive(&self, index: u32) -> Keypair {
+        let mut data = DOMAIN.to_vec();
+        data.extend_from_slice(&self.seed);
+        data.extend_from_slice(&index.to_be_bytes());
+        let secret = SecretKey::from_bytes(&hash256(&data)).expect("32-byte secret");
+        let public = PublicKey::from(&secret);
+        Keypair { secret, public }
+    }
+
+    /// Derives the next unused keypair, returning it with its index.
+    pub fn next_keypair(&mut self) -> (u32, Keypair) {
+        let index = self.next_index;
+        self.next_index = self.next_index.checked_add(1).expect("key index space exhausted");
+        (index, self.derive(index))
+    }
+}
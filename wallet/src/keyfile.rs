@@ -0,0 +1,126 @@
+//! On-disk keyfiles.
+//!
+//! A keyfile holds the 32-byte ed25519 secret key; the public half is
+//! re-derived on load.  The first byte selects the layout:
+//!
+//! * `0x00 || secret` – unencrypted.
+//! * `0x01 || salt (16) || nonce (12) || ciphertext` – the secret sealed with
+//!   ChaCha20Poly1305 under a key derived from the passphrase with Argon2id.
+
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use argon2::Argon2;
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, Key, KeyInit, Nonce};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, SECRET_KEY_LENGTH};
+use rand::{rngs::OsRng, RngCore};
+
+const PLAIN: u8 = 0;
+const ENCRYPTED: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> io::Result<ChaCha20Poly1305> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|_| invalid("key derivation failed"))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Writes `kp` to `path`, encrypting the secret key when `passphrase` is
+/// given.
+pub fn save_keypair(kp: &Keypair, path: &Path, passphrase: Option<&str>) -> io::Result<()> {
+    let secret = kp.secret.as_bytes();
+    let bytes = match passphrase {
+        None => [&[PLAIN][..], secret].concat(),
+        Some(passphrase) => {
+            let mut salt = [0u8; SALT_LEN];
+            let mut nonce = [0u8; NONCE_LEN];
+            OsRng.fill_bytes(&mut salt);
+            OsRng.fill_bytes(&mut nonce);
+            let sealed = cipher(passphrase, &salt)?
+                .encrypt(Nonce::from_slice(&nonce), &secret[..])
+                .map_err(|_| invalid("encryption failed"))?;
+            [&[ENCRYPTED][..], &salt, &nonce, &sealed].concat()
+        }
+    };
+    fs::write(path, bytes)
+}
+
+/// Reads a keypair written by [`save_keypair`].
+///
+/// Fails with [`ErrorKind::InvalidInput`] if the file is encrypted and no
+/// passphrase was supplied, and [`ErrorKind::InvalidData`] if the passphrase
+/// is wrong or the file is malformed.
+pub fn load_keypair(path: &Path, passphrase: Option<&str>) -> io::Result<Keypair> {
+    let bytes = fs::read(path)?;
+    let secret = match bytes.split_first() {
+        Some((&PLAIN, secret)) => secret.to_vec(),
+        Some((&ENCRYPTED, rest)) if rest.len() > SALT_LEN + NONCE_LEN => {
+            let passphrase = passphrase
+                .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "keyfile is encrypted"))?;
+            let (salt, rest) = rest.split_at(SALT_LEN);
+            let (nonce, sealed) = rest.split_at(NONCE_LEN);
+            cipher(passphrase, salt)?
+                .decrypt(Nonce::from_slice(nonce), sealed)
+                .map_err(|_| invalid("wrong passphrase or corrupt keyfile"))?
+        }
+        _ => return Err(invalid("unrecognised keyfile")),
+    };
+    if secret.len() != SECRET_KEY_LENGTH {
+        return Err(invalid("bad secret key length"));
+    }
+    let secret = SecretKey::from_bytes(&secret).map_err(|_| invalid("bad secret key"))?;
+    let public = PublicKey::from(&secret);
+    Ok(Keypair { secret, public })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("obscura-keyfile-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn encrypted_round_trip() {
+        let kp = Keypair::generate(&mut OsRng);
+        let path = temp_path("enc");
+        save_keypair(&kp, &path, Some("hunter2")).unwrap();
+        let loaded = load_keypair(&path, Some("hunter2")).unwrap();
+        assert_eq!(loaded.to_bytes(), kp.to_bytes());
+        assert_eq!(load_keypair(&path, None).unwrap_err().kind(), ErrorKind::InvalidInput);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn wrong_passphrase_fails() {
+        let kp = Keypair::generate(&mut OsRng);
+        let path = temp_path("wrong");
+        save_keypair(&kp, &path, Some("right")).unwrap();
+        let err = load_keypair(&path, Some("wrong")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn unencrypted_round_trip() {
+        let kp = Keypair::generate(&mut OsRng);
+        let path = temp_path("plain");
+        save_keypair(&kp, &path, None).unwrap();
+        assert_eq!(fs::read(&path).unwrap().len(), 1 + SECRET_KEY_LENGTH);
+        let loaded = load_keypair(&path, None).unwrap();
+        assert_eq!(loaded.to_bytes(), kp.to_bytes());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -2,6 +2,22 @@
 
 use ed25519_dalek::{Keypair, PublicKey, SecretKey};
 
+pub mod hd;
+pub mod keyfile;
+pub mod message;
+pub mod tracker;
+pub mod tx_builder;
+pub mod units;
+pub mod wallet;
+pub mod wasm;
+
+pub use obscura_core::address::Address;
+pub use hd::HdWallet;
+pub use keyfile::{load_keypair, save_keypair};
+pub use message::{sign_message, verify_message};
+pub use tracker::{TxStatus, TxTracker};
+pub use wallet::Wallet;
+
 /// Placeholder deterministic keypair (DO NOT USE IN PRODUCTION).
 pub fn generate_keypair() -> Keypair {
     // 32 zero bytes as secret — insecure placeholder.
@@ -0,0 +1,74 @@
+//! Signing arbitrary messages with a wallet key.
+//!
+//! A transaction signature covers the bare 32-byte
+//! [`Transaction::signing_message`](obscura_core::Transaction::signing_message).
+//! Message signatures instead cover
+//!
+//! ```text
+//! MESSAGE_TAG || len(context) as u64 BE || context || hash256(message)
+//! ```
+//!
+//! which is always longer than 32 bytes, so neither kind of signature ever
+//! verifies as the other, and signatures made under one `context` do not
+//! verify under another.
+
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier};
+use obscura_core::hash256;
+
+/// Domain tag leading every signed message preimage.
+pub const MESSAGE_TAG: &[u8] = b"Obscura Signed Message";
+
+/// The bytes actually signed for `message` under `context`.
+fn preimage(context: &str, message: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(MESSAGE_TAG.len() + 8 + context.len() + 32);
+    buf.extend_from_slice(MESSAGE_TAG);
+    buf.extend_from_slice(&(context.len() as u64).to_be_bytes());
+    buf.extend_from_slice(context.as_bytes());
+    buf.extend_from_slice(&hash256(message));
+    buf
+}
+
+/// Signs `message` under the application-chosen `context`, e.g. `"login"`.
+pub fn sign_message(kp: &Keypair, context: &str, message: &[u8]) -> Signature {
+    kp.sign(&preimage(context, message))
+}
+
+/// Checks a signature made by [`sign_message`] with the same `context`.
+pub fn verify_message(pubkey: &PublicKey, context: &str, message: &[u8], sig: &Signature) -> bool {
+    pubkey.verify(&preimage(context, message), sig).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SecretKey;
+    use obscura_core::{hash160, Transaction, TxInput, TxOutput};
+
+    #[test]
+    fn message_and_transaction_signatures_are_separated() {
+        let secret = SecretKey::from_bytes(&[5; 32]).unwrap();
+        let kp = Keypair { public: PublicKey::from(&secret), secret };
+        let prev = [TxOutput { value: 10, pubkey_hash: hash160(kp.public.as_bytes()).to_vec() }];
+        let mut tx = Transaction {
+            inputs: vec![TxInput::new([1; 32], 0, kp.public.as_bytes().to_vec(), vec![]).unwrap()],
+            outputs: vec![TxOutput { value: 9, pubkey_hash: vec![2] }],
+            metadata: None,
+        };
+        let msg = tx.signing_message();
+
+        // A message signature over the transaction's own signing hash is not
+        // a transaction signature...
+        let message_sig = sign_message(&kp, "tx", &msg);
+        assert!(verify_message(&kp.public, "tx", &msg, &message_sig));
+        assert!(!verify_message(&kp.public, "login", &msg, &message_sig));
+        tx.inputs[0].signature = message_sig.to_bytes().to_vec();
+        assert!(tx.verify_signatures(&prev).is_err());
+
+        // ...and a transaction signature is not a message signature.
+        let tx_sig = kp.sign(&msg);
+        tx.inputs[0].signature = tx_sig.to_bytes().to_vec();
+        assert!(tx.verify_signatures(&prev).is_ok());
+        assert!(!verify_message(&kp.public, "", &msg, &tx_sig));
+        assert!(!verify_message(&kp.public, "tx", &msg, &tx_sig));
+    }
+}
@@ -0,0 +1,156 @@
+//! Confirmation status of the wallet's own transactions across reorgs.
+//!
+//! A [`TxTracker`] is fed every block that extends the chain
+//! ([`TxTracker::connect_block`]) and every [`ReorgEvent`] the chain emits
+//! ([`TxTracker::apply_reorg`]).  It remembers which block confirmed (or
+//! conflicted) each tracked transaction, so disconnecting that block puts
+//! the transaction back to [`TxStatus::Pending`].
+
+use std::collections::HashMap;
+
+use obscura_core::{chain::Blockchain, chain::ReorgEvent, ledger::UtxoKey, Block, Hash, Transaction};
+
+/// Where a tracked transaction stands relative to the canonical chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxStatus {
+    /// In the canonical chain with this many confirmations (1 in the tip).
+    Confirmed(u64),
+    /// Not in the canonical chain yet; may still confirm.
+    Pending,
+    /// A different canonical transaction spent one of its inputs.
+    Conflicted,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Pending,
+    /// Included in `block` at `height`.
+    Confirmed { block: Hash, height: u64 },
+    /// Double-spent by a transaction in `block`.
+    Conflicted { block: Hash },
+}
+
+struct Tracked {
+    inputs: Vec<UtxoKey>,
+    state: State,
+}
+
+/// Per-transaction [`TxStatus`] for a set of tracked transactions.
+#[derive(Default)]
+pub struct TxTracker {
+    txs: HashMap<Hash, Tracked>,
+    /// Height of the last connected block.
+    height: u64,
+}
+
+impl TxTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts tracking `tx` as [`TxStatus::Pending`].  Re-tracking a known
+    /// transaction keeps its current status.
+    pub fn track(&mut self, tx: &Transaction) {
+        let inputs = tx.inputs.iter().map(|i| (i.prev_tx, i.output_index)).collect();
+        self.txs.entry(tx.hash()).or_insert(Tracked { inputs, state: State::Pending });
+    }
+
+    /// Status of `txid`, or `None` if it is not tracked.
+    pub fn status(&self, txid: &Hash) -> Option<TxStatus> {
+        Some(match self.txs.get(txid)?.state {
+            State::Pending => TxStatus::Pending,
+            State::Confirmed { height, .. } => TxStatus::Confirmed(self.height.saturating_sub(height) + 1),
+            State::Conflicted { .. } => TxStatus::Conflicted,
+        })
+    }
+
+    /// Records `block` as the new canonical tip: tracked transactions it
+    /// contains become confirmed, pending ones it double-spends conflicted.
+    pub fn connect_block(&mut self, block: &Block) {
+        let hash = block.hash();
+        let height = block.header.index;
+        self.height = height;
+        for tx in &block.transactions {
+            let txid = tx.hash();
+            if let Some(tracked) = self.txs.get_mut(&txid) {
+                tracked.state = State::Confirmed { block: hash, height };
+                continue;
+            }
+            for inp in &tx.inputs {
+                let key = (inp.prev_tx, inp.output_index);
+                for tracked in self.txs.values_mut() {
+                    if matches!(tracked.state, State::Pending) && tracked.inputs.contains(&key) {
+                        tracked.state = State::Conflicted { block: hash };
+                    }
+                }
+            }
+        }
+    }
+
+    /// Applies a reorg reported by [`Blockchain::subscribe_reorgs`]:
+    /// transactions confirmed or conflicted by a disconnected block return
+    /// to pending, then the new branch is connected.  `chain` must already
+    /// reflect the reorg so the connected blocks can be looked up.
+    pub fn apply_reorg(&mut self, event: &ReorgEvent, chain: &Blockchain) {
+        for tracked in self.txs.values_mut() {
+            let block = match tracked.state {
+                State::Confirmed { block, .. } | State::Conflicted { block } => block,
+                State::Pending => continue,
+            };
+            if event.disconnected.contains(&block) {
+                tracked.state = State::Pending;
+            }
+        }
+        for hash in &event.connected {
+            if let Some(block) = chain.get_block_by_hash(hash) {
+                self.connect_block(block);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Address, HdWallet, Wallet};
+    use obscura_core::TxOutput;
+
+    fn coinbase(height: u64, tag: u8) -> Transaction {
+        Transaction::coinbase(height, vec![TxOutput { value: 50, pubkey_hash: vec![tag] }], b"")
+    }
+
+    #[test]
+    fn reorg_excluding_block_makes_confirmed_tx_pending() {
+        let mut wallet = Wallet::new(HdWallet::from_seed([1; 32]), "main");
+        let addr = wallet.new_address();
+        let genesis_coinbase =
+            Transaction::coinbase(1, vec![TxOutput { value: 50, pubkey_hash: addr.pubkey_hash() }], b"");
+        let genesis = Block::new(1, [0u8; 32], vec![genesis_coinbase], 0);
+        let mut chain = Blockchain::new(genesis.clone()).unwrap();
+        let reorgs = chain.subscribe_reorgs();
+
+        let tx = wallet.build_spend(&chain.ledger, &Address::from_pubkey_hash("main", &[5; 20]).unwrap(), 40, 1).unwrap();
+        let mut tracker = TxTracker::new();
+        tracker.track(&tx);
+        assert_eq!(tracker.status(&tx.hash()), Some(TxStatus::Pending));
+
+        let a2 = Block::new(2, genesis.hash(), vec![coinbase(2, 2), tx.clone()], 0);
+        let a3 = Block::new(3, a2.hash(), vec![coinbase(3, 3)], 0);
+        for block in [a2, a3] {
+            chain.add_block(block.clone()).unwrap();
+            tracker.connect_block(&block);
+        }
+        assert_eq!(tracker.status(&tx.hash()), Some(TxStatus::Confirmed(2)));
+
+        let b2 = Block::new(2, genesis.hash(), vec![coinbase(2, 20)], 0);
+        let b3 = Block::new(3, b2.hash(), vec![coinbase(3, 30)], 0);
+        let b4 = Block::new(4, b3.hash(), vec![coinbase(4, 40)], 0);
+        for block in [b2, b3, b4] {
+            chain.add_block(block).unwrap();
+        }
+        let event = reorgs.try_recv().unwrap();
+        tracker.apply_reorg(&event, &chain);
+        assert_eq!(tracker.status(&tx.hash()), Some(TxStatus::Pending));
+        assert_eq!(tracker.status(&[0xEE; 32]), None);
+    }
+}
@@ -0,0 +1,276 @@
+//! Transaction construction with pluggable coin selection.
+//!
+//! [`TxBuilder`] turns a set of spendable UTXOs plus a list of payments into
+//! an unsigned [`Transaction`].  Which UTXOs are spent is delegated to a
+//! [`CoinSelector`] so wallets can trade off fees, privacy and dust:
+//!
+//! * [`LargestFirst`] – fewest inputs, usually leaves change.
+//! * [`SmallestFirst`] – consolidates small outputs.
+//! * [`BranchAndBound`] – searches for an exact match so no change output is
+//!   created, falling back to largest-first.
+
+use std::cmp::Reverse;
+
+use obscura_core::{ledger::UtxoKey, Error, Result, Transaction, TxInput, TxOutput};
+
+/// A spendable output together with the key identifying it.
+pub type Candidate = (UtxoKey, TxOutput);
+
+/// Strategy choosing which UTXOs fund a transaction.
+pub trait CoinSelector {
+    /// Returns a subset of `candidates` whose values sum to at least
+    /// `target` (amount + fee), or `None` if the funds are insufficient.
+    fn select(&self, candidates: &[Candidate], target: u64) -> Option<Vec<Candidate>>;
+}
+
+/// Accumulates candidates in the given order until `target` is reached.
+fn accumulate<'a>(ordered: impl Iterator<Item = &'a Candidate>, target: u64) -> Option<Vec<Candidate>> {
+    let mut chosen = Vec::new();
+    let mut total = 0u64;
+    for c in ordered {
+        if total >= target {
+            break;
+        }
+        total = total.checked_add(c.1.value)?;
+        chosen.push(c.clone());
+    }
+    (total >= target).then_some(chosen)
+}
+
+/// Spends the largest outputs first.
+pub struct LargestFirst;
+
+impl CoinSelector for LargestFirst {
+    fn select(&self, candidates: &[Candidate], target: u64) -> Option<Vec<Candidate>> {
+        let mut sorted: Vec<&Candidate> = candidates.iter().collect();
+        sorted.sort_by_key(|c| Reverse(c.1.value));
+        accumulate(sorted.into_iter(), target)
+    }
+}
+
+/// Spends the smallest outputs first.
+pub struct SmallestFirst;
+
+impl CoinSelector for SmallestFirst {
+    fn select(&self, candidates: &[Candidate], target: u64) -> Option<Vec<Candidate>> {
+        let mut sorted: Vec<&Candidate> = candidates.iter().collect();
+        sorted.sort_by_key(|c| c.1.value);
+        accumulate(sorted.into_iter(), target)
+    }
+}
+
+/// Depth-first search for a subset summing *exactly* to the target, which
+/// avoids creating a change output.
+///
+/// The search visits at most `max_tries` nodes; if no exact match is found
+/// it falls back to [`LargestFirst`].
+pub struct BranchAndBound {
+    pub max_tries: usize,
+}
+
+impl Default for BranchAndBound {
+    fn default() -> Self {
+        Self { max_tries: 100_000 }
+    }
+}
+
+impl BranchAndBound {
+    /// Iterative depth-first search, so deep candidate lists cannot
+    /// overflow the stack.  Including `sorted[idx]` is tried before
+    /// skipping it.
+    fn search(&self, sorted: &[&Candidate], suffix: &[u64], target: u64) -> Option<Vec<usize>> {
+        enum Step {
+            Visit { idx: usize, remaining: u64 },
+            Unpick,
+        }
+
+        let mut picked = Vec::new();
+        let mut tries = 0usize;
+        let mut stack = vec![Step::Visit { idx: 0, remaining: target }];
+        while let Some(step) = stack.pop() {
+            let Step::Visit { idx, remaining } = step else {
+                picked.pop();
+                continue;
+            };
+            if remaining == 0 {
+                return Some(picked);
+            }
+            tries += 1;
+            // Out of candidates, out of budget, or the rest cannot reach target.
+            if idx == sorted.len() || tries > self.max_tries || suffix[idx] < remaining {
+                continue;
+            }
+            // Pushed in reverse order of exploration.
+            stack.push(Step::Visit { idx: idx + 1, remaining });
+            let value = sorted[idx].1.value;
+            if value <= remaining {
+                picked.push(idx);
+                stack.push(Step::Unpick);
+                stack.push(Step::Visit { idx: idx + 1, remaining: remaining - value });
+            }
+        }
+        None
+    }
+}
+
+impl CoinSelector for BranchAndBound {
+    fn select(&self, candidates: &[Candidate], target: u64) -> Option<Vec<Candidate>> {
+        let mut sorted: Vec<&Candidate> = candidates.iter().collect();
+        sorted.sort_by_key(|c| Reverse(c.1.value));
+        // suffix[i] = sum of values from i to the end, used for pruning.
+        let mut suffix = vec![0u64; sorted.len() + 1];
+        for i in (0..sorted.len()).rev() {
+            suffix[i] = suffix[i + 1].saturating_add(sorted[i].1.value);
+        }
+        match self.search(&sorted, &suffix, target) {
+            Some(picked) => Some(picked.into_iter().map(|i| sorted[i].clone()).collect()),
+            None => LargestFirst.select(candidates, target),
+        }
+    }
+}
+
+/// Fluent builder producing an unsigned [`Transaction`].
+///
+/// Inputs carry `pubkey` but an empty signature and are already normalized;
+/// the caller signs the result.
+pub struct TxBuilder {
+    utxos: Vec<Candidate>,
+    outputs: Vec<TxOutput>,
+    fee: u64,
+    pubkey: Vec<u8>,
+    change_pkh: Vec<u8>,
+    selector: Box<dyn CoinSelector>,
+}
+
+impl TxBuilder {
+    /// Starts a builder spending outputs owned by `pubkey` and returning any
+    /// change to `change_pkh`.  Uses [`LargestFirst`] selection by default.
+    pub fn new(pubkey: Vec<u8>, change_pkh: Vec<u8>) -> Self {
+        Self {
+            utxos: Vec::new(),
+            outputs: Vec::new(),
+            fee: 0,
+            pubkey,
+            change_pkh,
+            selector: Box::new(LargestFirst),
+        }
+    }
+
+    /// Sets the UTXOs available for selection.
+    pub fn utxos(mut self, utxos: Vec<Candidate>) -> Self {
+        self.utxos = utxos;
+        self
+    }
+
+    /// Adds a payment of `value` to `pubkey_hash`.
+    pub fn pay(mut self, pubkey_hash: Vec<u8>, value: u64) -> Self {
+        self.outputs.push(TxOutput { value, pubkey_hash });
+        self
+    }
+
+    pub fn fee(mut self, fee: u64) -> Self {
+        self.fee = fee;
+        self
+    }
+
+    pub fn selector(mut self, selector: Box<dyn CoinSelector>) -> Self {
+        self.selector = selector;
+        self
+    }
+
+    /// Selects inputs and assembles the transaction.  A change output is
+    /// appended only when the selected inputs exceed amount + fee.
+    pub fn finish(self) -> Result<Transaction> {
+        let amount = self
+            .outputs
+            .iter()
+            .try_fold(0u64, |acc, o| acc.checked_add(o.value))
+            .ok_or(Error::ValueOverflow)?;
+        let target = amount.checked_add(self.fee).ok_or(Error::ValueOverflow)?;
+        let chosen = self
+            .selector
+            .select(&self.utxos, target)
+            .ok_or(Error::InsufficientFunds)?;
+        let total: u64 = chosen.iter().map(|c| c.1.value).sum();
+
+        let inputs = chosen
+            .into_iter()
+            .map(|((prev_tx, output_index), _)| TxInput {
+                prev_tx,
+                output_index,
+                pubkey: self.pubkey.clone(),
+                signature: vec![],
+                scheme: obscura_core::crypto::ED25519,
+            })
+            .collect();
+        let mut outputs = self.outputs;
+        if total > target {
+            outputs.push(TxOutput { value: total - target, pubkey_hash: self.change_pkh });
+        }
+        let mut tx = Transaction { inputs, outputs, metadata: None };
+        tx.normalize();
+        Ok(tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(values: &[u64]) -> Vec<Candidate> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| (([i as u8; 32], 0), TxOutput { value: v, pubkey_hash: vec![1] }))
+            .collect()
+    }
+
+    fn sum(chosen: &[Candidate]) -> u64 {
+        chosen.iter().map(|c| c.1.value).sum()
+    }
+
+    #[test]
+    fn largest_and_smallest_first_order() {
+        let utxos = candidates(&[5, 20, 10]);
+        assert_eq!(sum(&LargestFirst.select(&utxos, 12).unwrap()), 20);
+        assert_eq!(sum(&SmallestFirst.select(&utxos, 12).unwrap()), 15);
+        assert!(LargestFirst.select(&utxos, 36).is_none());
+    }
+
+    #[test]
+    fn branch_and_bound_finds_exact_match() {
+        let utxos = candidates(&[40, 7, 13, 25, 3]);
+        let chosen = BranchAndBound::default().select(&utxos, 23).unwrap();
+        assert_eq!(sum(&chosen), 23);
+
+        let tx = TxBuilder::new(vec![9], vec![8])
+            .utxos(utxos)
+            .pay(vec![2], 20)
+            .fee(3)
+            .selector(Box::new(BranchAndBound::default()))
+            .finish()
+            .unwrap();
+        assert_eq!(tx.outputs.len(), 1, "exact match needs no change output");
+    }
+
+    #[test]
+    fn builder_adds_change_when_overfunded() {
+        let tx = TxBuilder::new(vec![9], vec![8])
+            .utxos(candidates(&[40]))
+            .pay(vec![2], 20)
+            .fee(3)
+            .finish()
+            .unwrap();
+        assert_eq!(tx.inputs.len(), 1);
+        assert_eq!(tx.outputs[1].value, 17);
+        assert_eq!(tx.outputs[1].pubkey_hash, vec![8]);
+    }
+
+    #[test]
+    fn branch_and_bound_handles_deep_searches() {
+        // An exact match needs every candidate, one level deep each.
+        let utxos = candidates(&vec![1; 200_000]);
+        let chosen = BranchAndBound { max_tries: 1_000_000 }.select(&utxos, 200_000).unwrap();
+        assert_eq!(chosen.len(), 200_000);
+    }
+}
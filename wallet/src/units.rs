@@ -0,0 +1,74 @@
+//! Conversion between user-facing coin amounts and on-chain base units.
+//!
+//! Output values are integers counting base units; users type and read
+//! whole coins with up to [`DECIMALS`] decimal places, e.g. `"1.5"` is
+//! `150_000_000` base units.
+
+use std::fmt;
+
+/// Decimal places of one coin.
+pub const DECIMALS: u32 = 8;
+
+/// Base units in one coin.
+pub const COIN: u64 = 10u64.pow(DECIMALS);
+
+/// Why [`parse_amount`] rejected its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseAmountError(&'static str);
+
+impl fmt::Display for ParseAmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for ParseAmountError {}
+
+/// Parses a decimal coin amount such as `"2"`, `"0.25"` or `"1.5"` into
+/// base units.
+///
+/// Rejects signs, exponents, empty parts, more than [`DECIMALS`] decimal
+/// places and amounts that overflow a `u64`.
+pub fn parse_amount(s: &str) -> Result<u64, ParseAmountError> {
+    let (whole, frac) = s.split_once('.').unwrap_or((s, ""));
+    let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !digits(whole) || !digits(frac) || s.ends_with('.') {
+        return Err(ParseAmountError("invalid amount"));
+    }
+    if frac.len() > DECIMALS as usize {
+        return Err(ParseAmountError("too many decimal places"));
+    }
+    let overflow = ParseAmountError("amount too large");
+    let whole: u64 = whole.parse().map_err(|_| overflow)?;
+    let frac: u64 = format!("{frac:0<width$}", width = DECIMALS as usize).parse().expect("only digits");
+    whole.checked_mul(COIN).and_then(|v| v.checked_add(frac)).ok_or(overflow)
+}
+
+/// Formats base units as a coin amount, trimming trailing zeros.
+pub fn format_amount(value: u64) -> String {
+    let frac = format!("{:0width$}", value % COIN, width = DECIMALS as usize);
+    let frac = frac.trim_end_matches('0');
+    if frac.is_empty() {
+        (value / COIN).to_string()
+    } else {
+        format!("{}.{frac}", value / COIN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_formats_amounts() {
+        assert_eq!(parse_amount("1.5"), Ok(150_000_000));
+        assert_eq!(parse_amount("0.00000001"), Ok(1));
+        assert_eq!(parse_amount("42"), Ok(42 * COIN));
+        for bad in ["", "abc", "1.", ".5", "-1", "1e3", "1.2.3", "0.000000001", "184467440738"] {
+            assert!(parse_amount(bad).is_err(), "{bad:?}");
+        }
+        assert_eq!(format_amount(150_000_000), "1.5");
+        assert_eq!(format_amount(42 * COIN), "42");
+        assert_eq!(parse_amount(&format_amount(123_456_789)), Ok(123_456_789));
+    }
+}
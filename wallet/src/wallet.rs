@@ -0,0 +1,167 @@
+//! The top-level wallet object used by the CLI and GUI.
+//!
+//! A [`Wallet`] owns an [`HdWallet`] and the addresses derived from it, and
+//! can additionally watch foreign addresses.  Balances are read from a
+//! [`Ledger`]; spends are funded from owned addresses only and signed with
+//! the matching derived keys.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use ed25519_dalek::Signer;
+use obscura_core::{address::version_for, ledger::Ledger, Error, Result, Transaction};
+
+use crate::{
+    hd::HdWallet,
+    tx_builder::{Candidate, TxBuilder},
+    Address,
+};
+
+pub struct Wallet {
+    hd: HdWallet,
+    /// Network the wallet's addresses are versioned for, see
+    /// [`Config::network`](obscura_core::config::Config::network).
+    network: String,
+    /// Owned addresses and the derivation index of their key.
+    owned: BTreeMap<Address, u32>,
+    /// Every tracked address, owned ones included.
+    watched: BTreeSet<Address>,
+}
+
+impl Wallet {
+    pub fn new(hd: HdWallet, network: &str) -> Self {
+        Self { hd, network: network.to_owned(), owned: BTreeMap::new(), watched: BTreeSet::new() }
+    }
+
+    /// Derives a fresh owned address.
+    pub fn new_address(&mut self) -> Address {
+        let (index, kp) = self.hd.next_keypair();
+        let address = Address::from_pubkey(&self.network, kp.public.as_bytes());
+        self.owned.insert(address, index);
+        self.watched.insert(address);
+        address
+    }
+
+    /// Tracks `address` without being able to spend from it.
+    pub fn watch(&mut self, address: Address) {
+        self.watched.insert(address);
+    }
+
+    /// Every tracked address in ascending order.
+    pub fn addresses(&self) -> impl Iterator<Item = &Address> {
+        self.watched.iter()
+    }
+
+    /// Confirmed balance summed across owned addresses, i.e. what the
+    /// wallet can spend.
+    pub fn balance(&self, ledger: &Ledger) -> u64 {
+        Self::sum_balances(ledger, self.owned.keys())
+    }
+
+    /// Confirmed balance summed across watch-only addresses.
+    pub fn watched_balance(&self, ledger: &Ledger) -> u64 {
+        Self::sum_balances(ledger, self.watched.iter().filter(|a| !self.owned.contains_key(a)))
+    }
+
+    fn sum_balances<'a>(ledger: &Ledger, addresses: impl Iterator<Item = &'a Address>) -> u64 {
+        addresses.map(|a| ledger.balance_for_pubkey_hash(&a.pubkey_hash())).fold(0, u64::saturating_add)
+    }
+
+    /// Builds and signs a transaction paying `amount` to `to` plus `fee`,
+    /// funded from owned addresses.  Change returns to the first derived
+    /// address.
+    ///
+    /// Errors with [`Error::WrongNetwork`] if `to` belongs to another
+    /// network and with [`Error::InsufficientFunds`] if owned outputs cannot
+    /// cover the payment.
+    pub fn build_spend(&self, ledger: &Ledger, to: &Address, amount: u64, fee: u64) -> Result<Transaction> {
+        if to.version != version_for(&self.network) {
+            return Err(Error::WrongNetwork);
+        }
+        let (change, _) = self.owned.iter().min_by_key(|(_, &index)| index).ok_or(Error::InsufficientFunds)?;
+        let utxos: Vec<Candidate> = ledger
+            .utxos_sorted()
+            .into_iter()
+            .filter(|(_, out)| self.owner_index(&out.pubkey_hash).is_some())
+            .map(|(key, out)| (key, out.clone()))
+            .collect();
+        let mut tx = TxBuilder::new(Vec::new(), change.pubkey_hash())
+            .utxos(utxos)
+            .pay(to.pubkey_hash(), amount)
+            .fee(fee)
+            .finish()?;
+
+        // Inputs may come from several addresses, each with its own key.
+        let mut keys = Vec::with_capacity(tx.inputs.len());
+        for inp in &mut tx.inputs {
+            let spent = &ledger.utxos[&(inp.prev_tx, inp.output_index)];
+            let index = self.owner_index(&spent.pubkey_hash).expect("selected from owned outputs");
+            let kp = self.hd.derive(index);
+            inp.pubkey = kp.public.as_bytes().to_vec();
+            keys.push(kp);
+        }
+        let msg = tx.signing_message();
+        for (inp, kp) in tx.inputs.iter_mut().zip(&keys) {
+            inp.signature = kp.sign(&msg).to_bytes().to_vec();
+        }
+        Ok(tx)
+    }
+
+    fn owner_index(&self, pubkey_hash: &[u8]) -> Option<u32> {
+        let address = Address::from_pubkey_hash(&self.network, pubkey_hash)?;
+        self.owned.get(&address).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use obscura_core::{Block, TxOutput};
+
+    fn foreign(byte: u8) -> Address {
+        Address::from_pubkey_hash("main", &[byte; 20]).unwrap()
+    }
+
+    fn fixture(wallet: &mut Wallet) -> (Ledger, Address, Address) {
+        let (a, b) = (wallet.new_address(), wallet.new_address());
+        let pay = |addr: &Address, value| TxOutput { value, pubkey_hash: addr.pubkey_hash() };
+        let coinbase = Transaction::coinbase(1, vec![pay(&a, 30), pay(&b, 20), pay(&foreign(9), 7)], b"");
+        let genesis = Block::new(1, [0u8; 32], vec![coinbase], 0);
+        (Ledger::new(&genesis).unwrap(), a, b)
+    }
+
+    #[test]
+    fn derived_addresses_are_deterministic_and_distinct() {
+        let mut one = Wallet::new(HdWallet::from_seed([1; 32]), "main");
+        let mut two = Wallet::new(HdWallet::from_seed([1; 32]), "main");
+        let first = one.new_address();
+        assert_eq!(first, two.new_address());
+        assert_ne!(first, one.new_address());
+        assert_eq!(Address::parse(&first.to_string(), "main"), Ok(first));
+    }
+
+    #[test]
+    fn balance_excludes_watched_addresses() {
+        let mut wallet = Wallet::new(HdWallet::from_seed([1; 32]), "main");
+        let (ledger, _, _) = fixture(&mut wallet);
+        assert_eq!((wallet.balance(&ledger), wallet.watched_balance(&ledger)), (50, 0));
+        wallet.watch(foreign(9));
+        assert_eq!((wallet.balance(&ledger), wallet.watched_balance(&ledger)), (50, 7));
+    }
+
+    #[test]
+    fn spend_across_addresses_validates() {
+        let mut wallet = Wallet::new(HdWallet::from_seed([1; 32]), "main");
+        let (mut ledger, a, _) = fixture(&mut wallet);
+        wallet.watch(foreign(9));
+        let to = foreign(5);
+        let tx = wallet.build_spend(&ledger, &to, 45, 2).unwrap();
+        assert_eq!(tx.inputs.len(), 2, "needs both owned outputs, never the watched one");
+        assert_eq!(wallet.build_spend(&ledger, &to, 49, 2).err(), Some(Error::InsufficientFunds));
+
+        let block = Block::new(2, ledger.tip, vec![Transaction::coinbase(2, vec![], b""), tx], 0);
+        ledger.apply_block(&block).unwrap();
+        assert_eq!(ledger.balance_for_pubkey_hash(&to.pubkey_hash()), 45);
+        assert_eq!(ledger.balance_for_pubkey_hash(&a.pubkey_hash()), 3);
+        assert_eq!((wallet.balance(&ledger), wallet.watched_balance(&ledger)), (3, 7));
+    }
+}
@@ -0,0 +1,95 @@
+//! Byte-oriented wallet API suitable for browsers.
+//!
+//! `Keypair` and `Transaction` cannot cross the `wasm-bindgen` boundary, so
+//! these functions take and return plain byte vectors and hex strings:
+//!
+//! * keypairs are the 64-byte `secret || public` encoding,
+//! * transactions are `bincode`-encoded [`Transaction`]s.
+//!
+//! The functions are always compiled so native callers and tests can use
+//! them; the `wasm` feature additionally exports them through `wasm-bindgen`
+//! and routes `OsRng` through the browser's `crypto.getRandomValues`.
+
+use ed25519_dalek::{Keypair, Signer};
+use obscura_core::codec::{BincodeCodec, Codec};
+use rand::rngs::OsRng;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// Generates a fresh random keypair, returned as 64 bytes.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_keypair() -> Vec<u8> {
+    Keypair::generate(&mut OsRng).to_bytes().to_vec()
+}
+
+/// Derives the hex-encoded address (`hash160` of the public key) for a
+/// 32-byte public key.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn derive_address(pubkey: &[u8]) -> Result<String, String> {
+    if pubkey.len() != ed25519_dalek::PUBLIC_KEY_LENGTH {
+        return Err("public key must be 32 bytes".into());
+    }
+    Ok(hex::encode(obscura_core::hash160(pubkey)))
+}
+
+/// Returns the 32-byte public half of an encoded keypair.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn public_key(keypair: &[u8]) -> Result<Vec<u8>, String> {
+    let kp = Keypair::from_bytes(keypair).map_err(|e| e.to_string())?;
+    Ok(kp.public.as_bytes().to_vec())
+}
+
+/// Signs every input of the encoded transaction `tx` whose `pubkey` matches
+/// `keypair`, returning the re-encoded signed transaction.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn sign_transaction(keypair: &[u8], tx: &[u8]) -> Result<Vec<u8>, String> {
+    let kp = Keypair::from_bytes(keypair).map_err(|e| e.to_string())?;
+    let mut tx = BincodeCodec.decode_transaction(tx).map_err(|e| e.to_string())?;
+    let sig = kp.sign(&tx.signing_message()).to_bytes().to_vec();
+    let ours = kp.public.as_bytes();
+    for inp in tx.inputs.iter_mut().filter(|inp| inp.pubkey == ours) {
+        inp.signature = sig.clone();
+    }
+    BincodeCodec.encode_transaction(&tx).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use obscura_core::{ledger::Ledger, Block, Error, Transaction, TxInput, TxOutput};
+
+    #[test]
+    fn byte_api_signatures_verify_in_ledger() {
+        let kp = generate_keypair();
+        let pubkey = public_key(&kp).unwrap();
+        let address = hex::decode(derive_address(&pubkey).unwrap()).unwrap();
+        assert_eq!(address.len(), 20);
+
+        let coinbase = Transaction {
+            inputs: vec![],
+            outputs: vec![TxOutput { value: 50, pubkey_hash: address.clone() }],
+            metadata: None,
+        };
+        let genesis = Block::new(1, [0u8; 32], vec![coinbase.clone()], 0);
+        let ledger = Ledger::new(&genesis).unwrap();
+
+        let spend = Transaction {
+            inputs: vec![TxInput { prev_tx: coinbase.hash(), output_index: 0, pubkey, signature: vec![], scheme: 0 }],
+            outputs: vec![TxOutput { value: 50, pubkey_hash: vec![1] }],
+            metadata: None,
+        };
+        let encoded = BincodeCodec.encode_transaction(&spend).unwrap();
+        let signed = sign_transaction(&kp, &encoded).unwrap();
+        let mut signed = BincodeCodec.decode_transaction(&signed).unwrap();
+        assert_eq!(ledger.validate_tx(&signed), Ok(()));
+
+        signed.outputs[0].value = 49;
+        assert_eq!(ledger.validate_tx(&signed), Err(Error::BadSignature));
+    }
+
+    #[test]
+    fn malformed_inputs_are_errors() {
+        assert!(derive_address(&[0u8; 5]).is_err());
+        assert!(sign_transaction(&[0u8; 10], &[]).is_err());
+    }
+}